@@ -1,7 +1,12 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use wad_rs::flat::{flat_lumps, Flat};
+use wad_rs::graphics::{Palette, PaletteSet};
 use wad_rs::index::LumpNode;
 use wad_rs::lump::LumpRef;
+use wad_rs::rgba::RgbaTable;
+use wad_rs::sprite::{encode_patch, Sprite, SpriteHeader};
 use wad_rs::WadIndex;
+use std::hint::black_box;
 
 const WAD_DATA: &[u8] = include_bytes!("../assets/wad/freedoom1.wad").as_slice();
 
@@ -62,7 +67,7 @@ fn bench_converting_audio(b: &mut Criterion) {
         b.iter(|| {
             for lump_ref in &filtered_lumps {
                 let data = lump_ref.data();
-                let _sample = wad_rs::audio::SoundSample::try_from(data.as_ref()).unwrap();
+                let _sample = wad_rs::audio::SoundSample::try_from(data).unwrap();
             }
         })
     });
@@ -70,10 +75,109 @@ fn bench_converting_audio(b: &mut Criterion) {
     group.finish();
 }
 
+/// The per pixel conversion the pictures used before `RgbaTable`
+fn indexed_to_rgba_scalar(indices: &[u8], palette: &Palette) -> Vec<u8> {
+    let mut pixel_buffer = vec![0u8; indices.len() * 4];
+    for (&index, rgba) in indices.iter().zip(pixel_buffer.chunks_exact_mut(4)) {
+        rgba.copy_from_slice(palette.get_rgba(index as usize).unwrap().as_ref());
+    }
+    pixel_buffer
+}
+
+fn bench_palette_conversion(b: &mut Criterion) {
+    let wad_data = WAD_DATA;
+    let wad = WadIndex::from_bytes("freedoom1.wad".to_string(), &wad_data).unwrap();
+    let playpal = match wad.get_lump(vec![], "PLAYPAL") {
+        Some(LumpNode::Lump { lump, .. }) => lump.data(),
+        _ => panic!("PLAYPAL missing"),
+    };
+    let palette = PaletteSet::from_bytes(playpal).unwrap().normal();
+    let flats: Vec<&[u8]> = flat_lumps(&wad)
+        .iter()
+        .filter_map(|(_, lump)| Flat::new(lump.data()).ok())
+        .map(|flat| flat.indexed_pixels())
+        .collect();
+    let pixel_count: usize = flats.iter().map(|pixels| pixels.len()).sum();
+
+    let mut group = b.benchmark_group("Palette to RGBA");
+    group.throughput(Throughput::Elements(pixel_count as u64));
+    group.sample_size(100);
+    group.bench_function("scalar_get_rgba", |b| {
+        b.iter(|| {
+            for pixels in &flats {
+                black_box(indexed_to_rgba_scalar(pixels, &palette));
+            }
+        })
+    });
+    group.bench_function("rgba_table", |b| {
+        b.iter(|| {
+            let table = RgbaTable::new(&palette);
+            for pixels in &flats {
+                black_box(table.convert(pixels));
+            }
+        })
+    });
+
+    let mask: Vec<bool> = (0..64 * 64).map(|i| i % 5 != 0).collect();
+    group.bench_function("rgba_table_masked", |b| {
+        b.iter(|| {
+            let table = RgbaTable::new(&palette);
+            for pixels in &flats {
+                black_box(table.convert_masked(pixels, &mask));
+            }
+        })
+    });
+
+    group.finish();
+
+    // patches made from the flats, with every fifth pixel transparent
+    let patches: Vec<Vec<u8>> = flats
+        .iter()
+        .map(|pixels| {
+            let mut rgba = RgbaTable::new(&palette).convert(pixels);
+            for pixel in rgba.chunks_exact_mut(4).step_by(5) {
+                pixel[3] = 0;
+            }
+            let header = SpriteHeader {
+                width: 64,
+                height: 64,
+                left_offset: 0,
+                top_offset: 0,
+            };
+            encode_patch(header, &rgba, &palette).unwrap()
+        })
+        .collect();
+    let sprites: Vec<Sprite> = patches
+        .iter()
+        .map(|patch| Sprite::new(patch).unwrap())
+        .collect();
+
+    let mut group = b.benchmark_group("Sprite to RGBA");
+    group.throughput(Throughput::Elements(pixel_count as u64));
+    group.bench_function("rgba_pixel_buffer", |b| {
+        b.iter(|| {
+            for sprite in &sprites {
+                black_box(sprite.rgba_pixel_buffer(&palette).unwrap());
+            }
+        })
+    });
+    group.bench_function("indexed_image_then_table", |b| {
+        b.iter(|| {
+            let table = RgbaTable::new(&palette);
+            for sprite in &sprites {
+                let image = sprite.indexed_image().unwrap();
+                black_box(table.convert_masked(image.pixels(), image.mask()));
+            }
+        })
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_wad_from_bytes,
     // bench_indexing_lumps,
     bench_converting_audio,
+    bench_palette_conversion,
 );
 criterion_main!(benches);
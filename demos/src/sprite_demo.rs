@@ -40,13 +40,10 @@ fn main() {
         //     sprite.top_offset(),
         // );
 
-        let file = std::fs::File::create(format!("assets/img/{}.png", name.replace("/", "_"))).unwrap();
-        let data = sprite.rgba_pixel_buffer(&palette).unwrap();
-        let mut encoder = png::Encoder::new(file, sprite.width() as u32, sprite.height() as u32);
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
-        writer.write_image_data(&data).unwrap();
+        let image = sprite.indexed_image().unwrap();
+        let offsets = Some((sprite.left_offset() as i32, sprite.top_offset() as i32));
+        let data = wad_rs::export::encode_png_indexed(&image, &palette, offsets).unwrap();
+        std::fs::write(format!("assets/img/{}.png", name.replace("/", "_")), data).unwrap();
     }
     println!("Extracted {} sprites", count);
 }
\ No newline at end of file
@@ -0,0 +1,414 @@
+use crate::index::{LumpNode, LumpSource};
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Size of a record of the ANIMATED lump
+const ANIMATED_RECORD_SIZE: usize = 23;
+/// Size of a record of the SWITCHES lump
+const SWITCHES_RECORD_SIZE: usize = 20;
+/// Size of a zero terminated name in ANIMATED and SWITCHES records
+const NAME_SIZE: usize = 9;
+/// Type byte which ends the ANIMATED lump
+const ANIMATED_END: u8 = 0xFF;
+/// Tics per frame of the vanilla animations
+const VANILLA_SPEED: i32 = 8;
+
+/// Animated flats and textures of Doom and Doom II (`animdefs` of p_spec.c) as
+/// (is texture, last frame, first frame)
+const VANILLA_ANIMATIONS: &[(bool, &str, &str)] = &[
+    (false, "NUKAGE3", "NUKAGE1"),
+    (false, "FWATER4", "FWATER1"),
+    (false, "SWATER4", "SWATER1"),
+    (false, "LAVA4", "LAVA1"),
+    (false, "BLOOD3", "BLOOD1"),
+    (false, "RROCK08", "RROCK05"),
+    (false, "SLIME04", "SLIME01"),
+    (false, "SLIME08", "SLIME05"),
+    (false, "SLIME12", "SLIME09"),
+    (true, "BLODGR4", "BLODGR1"),
+    (true, "SLADRIP3", "SLADRIP1"),
+    (true, "BLODRIP4", "BLODRIP1"),
+    (true, "FIREWALL", "FIREWALA"),
+    (true, "GSTFONT3", "GSTFONT1"),
+    (true, "FIRELAVA", "FIRELAV3"),
+    (true, "FIREMAG3", "FIREMAG1"),
+    (true, "FIREBLU2", "FIREBLU1"),
+    (true, "ROCKRED3", "ROCKRED1"),
+    (true, "BFALL4", "BFALL1"),
+    (true, "SFALL4", "SFALL1"),
+    (true, "WFALL4", "WFALL1"),
+    (true, "DBRAIN4", "DBRAIN1"),
+];
+
+/// Switch textures of Doom and Doom II (`alphSwitchList` of p_switch.c) as
+/// (off texture, on texture, episode)
+const VANILLA_SWITCHES: &[(&str, &str, u16)] = &[
+    ("SW1BRCOM", "SW2BRCOM", 1),
+    ("SW1BRN1", "SW2BRN1", 1),
+    ("SW1BRN2", "SW2BRN2", 1),
+    ("SW1BRNGN", "SW2BRNGN", 1),
+    ("SW1BROWN", "SW2BROWN", 1),
+    ("SW1COMM", "SW2COMM", 1),
+    ("SW1COMP", "SW2COMP", 1),
+    ("SW1DIRT", "SW2DIRT", 1),
+    ("SW1EXIT", "SW2EXIT", 1),
+    ("SW1GRAY", "SW2GRAY", 1),
+    ("SW1GRAY1", "SW2GRAY1", 1),
+    ("SW1METAL", "SW2METAL", 1),
+    ("SW1PIPE", "SW2PIPE", 1),
+    ("SW1SLAD", "SW2SLAD", 1),
+    ("SW1STARG", "SW2STARG", 1),
+    ("SW1STON1", "SW2STON1", 1),
+    ("SW1STON2", "SW2STON2", 1),
+    ("SW1STONE", "SW2STONE", 1),
+    ("SW1STRTN", "SW2STRTN", 1),
+    ("SW1BLUE", "SW2BLUE", 2),
+    ("SW1CMT", "SW2CMT", 2),
+    ("SW1GARG", "SW2GARG", 2),
+    ("SW1GSTON", "SW2GSTON", 2),
+    ("SW1HOT", "SW2HOT", 2),
+    ("SW1LION", "SW2LION", 2),
+    ("SW1SATYR", "SW2SATYR", 2),
+    ("SW1SKIN", "SW2SKIN", 2),
+    ("SW1VINE", "SW2VINE", 2),
+    ("SW1WOOD", "SW2WOOD", 2),
+    ("SW1PANEL", "SW2PANEL", 3),
+    ("SW1ROCK", "SW2ROCK", 3),
+    ("SW1MET2", "SW2MET2", 3),
+    ("SW1WDMET", "SW2WDMET", 3),
+    ("SW1BRIK", "SW2BRIK", 3),
+    ("SW1MOD1", "SW2MOD1", 3),
+    ("SW1ZIM", "SW2ZIM", 3),
+    ("SW1STON6", "SW2STON6", 3),
+    ("SW1TEK", "SW2TEK", 3),
+    ("SW1MARB", "SW2MARB", 3),
+    ("SW1SKULL", "SW2SKULL", 3),
+];
+
+/// An animated flat or texture, every texture or flat from `first` to `last` is a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationDefinition {
+    /// `true` for wall textures, `false` for flats
+    pub is_texture: bool,
+    pub first: String,
+    pub last: String,
+    /// Tics each frame is shown
+    pub speed: i32,
+}
+
+/// A switch texture and the texture it changes to when it is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchDefinition {
+    pub off: String,
+    pub on: String,
+    /// 1 for shareware Doom, 2 for registered Doom, 3 for Doom II
+    pub episode: u16,
+}
+
+/// The frames of an animation, resolved against the texture or flat list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationFrames<'n> {
+    pub frames: Vec<&'n str>,
+    /// Tics each frame is shown
+    pub speed: i32,
+}
+
+/// Reads a zero terminated name, names are upper cased like lump names
+fn read_name(data: &[u8]) -> Result<String> {
+    let length = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    Ok(std::str::from_utf8(&data[..length])?.to_ascii_uppercase())
+}
+
+/// Parses a Boom ANIMATED lump.
+///
+/// # Structure of an ANIMATED Lump
+/// A list of 23 byte records, ended by a record with the type 0xFF:
+/// - 1 byte: Type, bit 0 set for wall textures, clear for flats
+/// - 9 bytes: Name of the last frame (zero terminated)
+/// - 9 bytes: Name of the first frame (zero terminated)
+/// - 4 bytes: Tics per frame (i32, little-endian)
+///
+/// # Returns
+/// - `Ok(Vec<AnimationDefinition>)` with the records before the end marker.
+/// - `Err` if a record is truncated or a name is no valid UTF-8.
+pub fn parse_animated(data: &[u8]) -> Result<Vec<AnimationDefinition>> {
+    let mut definitions = Vec::new();
+    for record in data.chunks(ANIMATED_RECORD_SIZE) {
+        if record[0] == ANIMATED_END {
+            return Ok(definitions);
+        }
+        if record.len() < ANIMATED_RECORD_SIZE {
+            return Err("ANIMATED record truncated".into());
+        }
+
+        definitions.push(AnimationDefinition {
+            is_texture: record[0] & 1 != 0,
+            last: read_name(&record[1..1 + NAME_SIZE])?,
+            first: read_name(&record[1 + NAME_SIZE..1 + 2 * NAME_SIZE])?,
+            speed: i32::from_le_bytes([record[19], record[20], record[21], record[22]]),
+        });
+    }
+
+    Ok(definitions)
+}
+
+/// Parses a Boom SWITCHES lump.
+///
+/// # Structure of a SWITCHES Lump
+/// A list of 20 byte records, ended by a record with the episode 0:
+/// - 9 bytes: Name of the off texture (zero terminated)
+/// - 9 bytes: Name of the on texture (zero terminated)
+/// - 2 bytes: Episode (u16, little-endian)
+///
+/// # Returns
+/// - `Ok(Vec<SwitchDefinition>)` with the records before the end marker.
+/// - `Err` if a record is truncated or a name is no valid UTF-8.
+pub fn parse_switches(data: &[u8]) -> Result<Vec<SwitchDefinition>> {
+    let mut definitions = Vec::new();
+    for record in data.chunks(SWITCHES_RECORD_SIZE) {
+        if record.len() < SWITCHES_RECORD_SIZE {
+            return Err("SWITCHES record truncated".into());
+        }
+
+        let episode = u16::from_le_bytes([record[18], record[19]]);
+        if episode == 0 {
+            return Ok(definitions);
+        }
+
+        definitions.push(SwitchDefinition {
+            off: read_name(&record[..NAME_SIZE])?,
+            on: read_name(&record[NAME_SIZE..2 * NAME_SIZE])?,
+            episode,
+        });
+    }
+
+    Ok(definitions)
+}
+
+/// The animations hard coded in vanilla Doom
+pub fn vanilla_animations() -> Vec<AnimationDefinition> {
+    VANILLA_ANIMATIONS
+        .iter()
+        .map(|&(is_texture, last, first)| AnimationDefinition {
+            is_texture,
+            first: first.to_string(),
+            last: last.to_string(),
+            speed: VANILLA_SPEED,
+        })
+        .collect()
+}
+
+/// The switches hard coded in vanilla Doom
+pub fn vanilla_switches() -> Vec<SwitchDefinition> {
+    VANILLA_SWITCHES
+        .iter()
+        .map(|&(off, on, episode)| SwitchDefinition {
+            off: off.to_string(),
+            on: on.to_string(),
+            episode,
+        })
+        .collect()
+}
+
+/// Animated flats, animated textures and switches of a game.
+///
+/// Like in the engine an animation is a range: the frames are all textures (in the order
+/// of TEXTURE1 and TEXTURE2) or flats (in lump order) from the first to the last frame.
+/// Renaming or reordering textures between them changes the animation.
+#[derive(Debug, Clone)]
+pub struct Animations {
+    animations: Vec<AnimationDefinition>,
+    switches: Vec<SwitchDefinition>,
+}
+
+impl Animations {
+    pub fn new(animations: Vec<AnimationDefinition>, switches: Vec<SwitchDefinition>) -> Self {
+        Self {
+            animations,
+            switches,
+        }
+    }
+
+    /// The animations and switches of vanilla Doom
+    pub fn vanilla() -> Self {
+        Self::new(vanilla_animations(), vanilla_switches())
+    }
+
+    /// Loads the ANIMATED and SWITCHES lumps of a lump tree. Like in Boom a lump replaces
+    /// the built-in table, the vanilla definitions are used for a missing lump.
+    pub fn from_source<'a, S: LumpSource<'a>>(source: &S) -> Result<Self> {
+        let animations = match source.get_lump(vec![], "ANIMATED") {
+            Some(LumpNode::Lump { lump, .. }) => parse_animated(&lump.decompressed()?)?,
+            _ => vanilla_animations(),
+        };
+        let switches = match source.get_lump(vec![], "SWITCHES") {
+            Some(LumpNode::Lump { lump, .. }) => parse_switches(&lump.decompressed()?)?,
+            _ => vanilla_switches(),
+        };
+
+        Ok(Self::new(animations, switches))
+    }
+
+    pub fn animations(&self) -> &[AnimationDefinition] {
+        &self.animations
+    }
+
+    pub fn switches(&self) -> &[SwitchDefinition] {
+        &self.switches
+    }
+
+    /// Resolves the frames of the animated texture a texture belongs to.
+    ///
+    /// # Arguments
+    /// - `name`: Any frame of the animation.
+    /// - `texture_names`: All texture names in the order of their definitions.
+    /// # Returns
+    /// - `Some(AnimationFrames)` with the frames from first to last.
+    /// - `None` if the texture is not animated or the range is broken, e.g. the last frame
+    ///   is defined before the first one.
+    pub fn texture_frames<'n>(
+        &self,
+        name: &str,
+        texture_names: &[&'n str],
+    ) -> Option<AnimationFrames<'n>> {
+        self.frames(true, name, texture_names)
+    }
+
+    /// Resolves the frames of the animated flat a flat belongs to, see `texture_frames`.
+    /// `flat_names` have to be in lump order.
+    pub fn flat_frames<'n>(
+        &self,
+        name: &str,
+        flat_names: &[&'n str],
+    ) -> Option<AnimationFrames<'n>> {
+        self.frames(false, name, flat_names)
+    }
+
+    /// Returns the texture a switch texture changes to, in both directions
+    pub fn switch_partner(&self, name: &str) -> Option<&str> {
+        self.switches.iter().find_map(|switch| {
+            if switch.off.eq_ignore_ascii_case(name) {
+                Some(switch.on.as_str())
+            } else if switch.on.eq_ignore_ascii_case(name) {
+                Some(switch.off.as_str())
+            } else {
+                None
+            }
+        })
+    }
+
+    fn frames<'n>(
+        &self,
+        is_texture: bool,
+        name: &str,
+        names: &[&'n str],
+    ) -> Option<AnimationFrames<'n>> {
+        let position = |frame: &str| {
+            names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(frame))
+        };
+        let frame = position(name)?;
+
+        self.animations
+            .iter()
+            .filter(|animation| animation.is_texture == is_texture)
+            .find_map(|animation| {
+                let first = position(&animation.first)?;
+                let last = position(&animation.last)?;
+                (first < last && (first..=last).contains(&frame)).then(|| AnimationFrames {
+                    frames: names[first..=last].to_vec(),
+                    speed: animation.speed,
+                })
+            })
+    }
+}
+
+impl Default for Animations {
+    fn default() -> Self {
+        Self::vanilla()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::wad::{WadIndex, WadWriter};
+
+    fn name(name: &str) -> [u8; NAME_SIZE] {
+        let mut bytes = [0u8; NAME_SIZE];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn animated_and_switches_lumps_can_be_parsed() {
+        let mut animated = vec![1];
+        animated.extend(name("wfall4"));
+        animated.extend(name("WFALL1"));
+        animated.extend(&4i32.to_le_bytes());
+        animated.push(ANIMATED_END);
+        let definitions = parse_animated(&animated).unwrap();
+        assert_eq!(
+            definitions,
+            vec![AnimationDefinition {
+                is_texture: true,
+                first: "WFALL1".to_string(),
+                last: "WFALL4".to_string(),
+                speed: 4,
+            }]
+        );
+        assert!(parse_animated(&animated[..10]).is_err());
+
+        let mut switches = Vec::new();
+        switches.extend(name("SW1FOO"));
+        switches.extend(name("SW2FOO"));
+        switches.extend(&3u16.to_le_bytes());
+        switches.extend(&[0u8; SWITCHES_RECORD_SIZE]);
+        let definitions = parse_switches(&switches).unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].on, "SW2FOO");
+    }
+
+    #[test]
+    fn animations_resolve_frames_and_switches() {
+        let animations = Animations::vanilla();
+        let textures = [
+            "BFALL1", "BFALL2", "BFALL3", "BFALL4", "SW1BRN1", "STARTAN3",
+        ];
+
+        let frames = animations.texture_frames("bfall3", &textures).unwrap();
+        assert_eq!(frames.frames, vec!["BFALL1", "BFALL2", "BFALL3", "BFALL4"]);
+        assert_eq!(frames.speed, 8);
+        assert!(animations.texture_frames("STARTAN3", &textures).is_none());
+        assert!(animations.flat_frames("BFALL1", &textures).is_none());
+
+        // the last frame defined before the first one breaks the animation
+        let flats = ["NUKAGE3", "NUKAGE1", "NUKAGE2"];
+        assert!(animations.flat_frames("NUKAGE1", &flats).is_none());
+
+        assert_eq!(animations.switch_partner("SW1BRN1"), Some("SW2BRN1"));
+        assert_eq!(animations.switch_partner("sw2skull"), Some("SW1SKULL"));
+        assert!(animations.switch_partner("STARTAN3").is_none());
+    }
+
+    #[test]
+    fn animations_prefer_lumps_over_vanilla_tables() {
+        let mut switches = Vec::new();
+        switches.extend(name("SW1FOO"));
+        switches.extend(name("SW2FOO"));
+        switches.extend(&1u16.to_le_bytes());
+
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_lump("SWITCHES", switches).unwrap();
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let animations = Animations::from_source(&index).unwrap();
+
+        assert_eq!(animations.switch_partner("SW1FOO"), Some("SW2FOO"));
+        assert!(animations.switch_partner("SW1BRN1").is_none());
+        assert_eq!(animations.animations(), Animations::vanilla().animations());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::graphics::Palette;
+use crate::sprite::Sprite;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// A rectangle relative to the origin of a sprite, the point the engine places on the
+/// position of a thing. `right` and `bottom` are exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Bounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Bounds {
+    /// Returns the area a sprite covers when it is drawn at the origin, mirrored or not.
+    ///
+    /// Like vanilla Doom's `R_ProjectSprite` the sprite is shifted left by its left offset
+    /// and up by its top offset. A mirrored sprite only has its columns reversed and keeps
+    /// the same area, ZDoom instead flips it around the origin (left edge at
+    /// `left_offset - width`).
+    pub fn of_sprite(sprite: &Sprite) -> Self {
+        let left = -(sprite.left_offset() as i32);
+        let top = -(sprite.top_offset() as i32);
+        let width = sprite.width() as i32;
+
+        Self {
+            left,
+            top,
+            right: left + width,
+            bottom: top + sprite.height() as i32,
+        }
+    }
+
+    /// Returns the smallest rectangle which covers the bounds of all sprites, e.g. all
+    /// frames of an animation, so they can be exported onto canvases of the same size.
+    ///
+    /// # Arguments
+    /// - `sprites`: The sprites.
+    /// # Returns
+    /// - `Some(Bounds)` covering all sprites.
+    /// - `None` if there are no sprites.
+    pub fn of_sprites<'s, 'a: 's, I>(sprites: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'s Sprite<'a>>,
+    {
+        sprites
+            .into_iter()
+            .map(Self::of_sprite)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// The smallest rectangle which covers both rectangles
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.right - self.left).max(0) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.bottom - self.top).max(0) as usize
+    }
+
+    /// The position of the origin on a canvas of the size of the bounds
+    pub fn origin(&self) -> (i32, i32) {
+        (-self.left, -self.top)
+    }
+}
+
+/// An RGBA image which sprites are drawn onto, row by row with 4 bytes per pixel.
+/// Uncovered pixels stay transparent (0, 0, 0, 0).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// Creates a fully transparent canvas
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    /// Creates a transparent canvas of the size of the bounds, draw the sprites at
+    /// `Bounds::origin` to place them inside it
+    pub fn from_bounds(bounds: &Bounds) -> Self {
+        Self::new(bounds.width(), bounds.height())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The RGBA pixels row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    /// Draws a sprite with its origin at a position of the canvas, see `Bounds::of_sprite`.
+    /// Later sprites are drawn over earlier ones, pixels outside the canvas are clipped.
+    ///
+    /// # Arguments
+    /// - `sprite`: The sprite to draw.
+    /// - `palette`: The palette the sprite is drawn with.
+    /// - `x`, `y`: The position of the origin, may be outside of the canvas.
+    /// - `mirrored`: Flip the sprite horizontally, as for the mirrored rotations of a frame.
+    /// # Returns
+    /// - `Ok(())` if the sprite was drawn.
+    /// - `Err` if the sprite data is invalid.
+    pub fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        palette: &Palette,
+        x: i32,
+        y: i32,
+        mirrored: bool,
+    ) -> Result<()> {
+        let bounds = Bounds::of_sprite(sprite);
+        let left = x + bounds.left;
+        let top = y + bounds.top;
+        let last_column = sprite.width() as i32 - 1;
+
+        sprite.for_each_post(|column, row, pixels| {
+            let column = column as i32;
+            let column = if mirrored {
+                last_column - column
+            } else {
+                column
+            };
+            let dest_x = left + column;
+            if dest_x < 0 || dest_x as usize >= self.width {
+                return Ok(());
+            }
+
+            for (dy, &index) in pixels.iter().enumerate() {
+                let dest_y = top + (row + dy) as i32;
+                if dest_y < 0 || dest_y as usize >= self.height {
+                    continue;
+                }
+
+                let pos = (dest_y as usize * self.width + dest_x as usize) * 4;
+                self.pixels[pos..pos + 4].copy_from_slice(
+                    palette
+                        .get_rgba(index as usize)
+                        .ok_or("palette index out of bounds")?
+                        .as_ref(),
+                );
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Draws every frame of an animation onto its own canvas. All canvases have the size of
+/// the union of the frame bounds and share the origin, so the frames line up like in the
+/// game.
+///
+/// # Arguments
+/// - `frames`: The sprites of the frames and whether they are drawn mirrored.
+/// - `palette`: The palette the frames are drawn with.
+/// # Returns
+/// - `Ok(Vec<Canvas>)` with one canvas per frame, empty if there are no frames.
+/// - `Err` if the data of a frame is invalid.
+pub fn compose_frames(frames: &[(Sprite, bool)], palette: &Palette) -> Result<Vec<Canvas>> {
+    let Some(bounds) = Bounds::of_sprites(frames.iter().map(|(sprite, _)| sprite)) else {
+        return Ok(Vec::new());
+    };
+    let (x, y) = bounds.origin();
+
+    frames
+        .iter()
+        .map(|(sprite, mirrored)| {
+            let mut canvas = Canvas::from_bounds(&bounds);
+            canvas.draw_sprite(sprite, palette, x, y, *mirrored)?;
+            Ok(canvas)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite::{SpriteHeader, encode_patch};
+
+    fn palette() -> Palette<'static> {
+        let mut colors = [[0u8; 3]; 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = [i as u8, 0, 0];
+        }
+        Palette::from_colors(colors)
+    }
+
+    /// Patch whose pixels have the red value `10 * (x + 1)`
+    fn patch(width: u16, height: u16, left_offset: i16, top_offset: i16) -> Vec<u8> {
+        let mut rgba = Vec::new();
+        for _ in 0..height {
+            for x in 0..width {
+                rgba.extend_from_slice(&[10 * (x as u8 + 1), 0, 0, 255]);
+            }
+        }
+        let header = SpriteHeader {
+            width,
+            height,
+            left_offset,
+            top_offset,
+        };
+        encode_patch(header, &rgba, &palette()).unwrap()
+    }
+
+    #[test]
+    fn sprites_are_placed_by_their_offsets() {
+        let data = patch(3, 2, 1, 2);
+        let sprite = Sprite::new(&data).unwrap();
+        let bounds = Bounds::of_sprite(&sprite);
+        assert_eq!(
+            (bounds.left, bounds.top, bounds.right, bounds.bottom),
+            (-1, -2, 2, 0)
+        );
+
+        let mut canvas = Canvas::new(4, 4);
+        canvas
+            .draw_sprite(&sprite, &palette(), 1, 2, false)
+            .unwrap();
+        let pixels = canvas.pixels();
+        assert_eq!(&pixels[0..4], &[10, 0, 0, 255]);
+        assert_eq!(&pixels[8..12], &[30, 0, 0, 255]);
+        assert_eq!(&pixels[12..16], &[0, 0, 0, 0]);
+        assert_eq!(&pixels[32..36], &[0, 0, 0, 0]);
+
+        // mirrored sprites cover the same area like in vanilla, only the columns are reversed
+        let mut canvas = Canvas::new(4, 4);
+        canvas.draw_sprite(&sprite, &palette(), 1, 2, true).unwrap();
+        let pixels = canvas.pixels();
+        assert_eq!(&pixels[0..4], &[30, 0, 0, 255]);
+        assert_eq!(&pixels[4..8], &[20, 0, 0, 255]);
+        assert_eq!(&pixels[8..12], &[10, 0, 0, 255]);
+        assert_eq!(&pixels[12..16], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn animation_frames_share_the_origin() {
+        let small = patch(2, 2, 1, 2);
+        let large = patch(4, 3, 3, 1);
+        let frames = [
+            (Sprite::new(&small).unwrap(), false),
+            (Sprite::new(&large).unwrap(), false),
+        ];
+
+        let bounds = Bounds::of_sprites(frames.iter().map(|(s, _)| s)).unwrap();
+        assert_eq!((bounds.width(), bounds.height()), (4, 4));
+        assert_eq!(bounds.origin(), (3, 2));
+
+        let canvases = compose_frames(&frames, &palette()).unwrap();
+        assert_eq!(canvases.len(), 2);
+        assert!(canvases.iter().all(|c| c.width() == 4 && c.height() == 4));
+        // the first column of the small frame is one pixel left of the origin
+        assert_eq!(&canvases[0].pixels()[8..12], &[10, 0, 0, 255]);
+        assert_eq!(&canvases[1].pixels()[0..4], &[0, 0, 0, 0]);
+        assert_eq!(&canvases[1].pixels()[16..20], &[10, 0, 0, 255]);
+
+        assert!(compose_frames(&[], &palette()).unwrap().is_empty());
+    }
+}
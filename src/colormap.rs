@@ -0,0 +1,215 @@
+use crate::graphics::Palette;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Size of a single colormap table in bytes
+pub const COLORMAP_TABLE_SIZE: usize = 256;
+/// Number of light level tables, table 0 is full bright and table 31 is the darkest
+pub const LIGHT_TABLES: usize = 32;
+/// Index of the invulnerability table
+pub const INVULNERABILITY_TABLE: usize = 32;
+/// Index of the all black table
+pub const BLACK_TABLE: usize = 33;
+
+// constants of the light table setup in the vanilla renderer (r_main.c)
+const LIGHT_LEVELS: i64 = 16;
+const LIGHT_SEG_SHIFT: u32 = 4;
+const MAX_LIGHT_Z: i64 = 128;
+const LIGHT_Z_SHIFT: u32 = 20;
+const LIGHT_SCALE_SHIFT: u32 = 12;
+const DIST_MAP: i64 = 2;
+const SCREEN_WIDTH: i64 = 320;
+const FRAC_BITS: u32 = 16;
+
+/// Returns the colormap table the vanilla renderer uses for a sector light level and distance.
+///
+/// This follows the distance light table (`zlight`) of the Doom renderer: the sector light
+/// is reduced to 16 levels and every level fades into darker tables with increasing distance,
+/// close surfaces are always drawn brighter than the sector light.
+///
+/// # Arguments
+/// - `light_level`: The sector light level (0-255).
+/// - `distance`: The distance from the viewer in map units.
+/// # Returns
+/// - The index of the light table, between 0 (full bright) and 31 (darkest).
+pub fn light_table(light_level: u8, distance: u32) -> usize {
+    let light = (light_level >> LIGHT_SEG_SHIFT) as i64;
+    let start_map = (LIGHT_LEVELS - 1 - light) * 2 * LIGHT_TABLES as i64 / LIGHT_LEVELS;
+
+    let z = ((distance >> (LIGHT_Z_SHIFT - FRAC_BITS)) as i64).min(MAX_LIGHT_Z - 1);
+    // FixedDiv(SCREEN_WIDTH / 2 * FRACUNIT, (z + 1) << LIGHTZSHIFT)
+    let scale =
+        (((SCREEN_WIDTH / 2) << (2 * FRAC_BITS)) / ((z + 1) << LIGHT_Z_SHIFT)) >> LIGHT_SCALE_SHIFT;
+
+    (start_map - scale / DIST_MAP).clamp(0, LIGHT_TABLES as i64 - 1) as usize
+}
+
+/// Represents a COLORMAP lump.
+///
+/// # Structure of a COLORMAP Lump
+/// The lump is a sequence of 256 byte tables, each one maps a palette index to the palette
+/// index which is drawn instead:
+/// - Tables 0-31: Light levels from full bright to darkest
+/// - Table 32: The invulnerability effect (inverted grayscale)
+/// - Table 33: All black, unused by the engine (missing in some WADs)
+#[derive(Debug, Clone)]
+pub struct Colormap<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Colormap<'a> {
+    /// Creates a `Colormap` from the lump data.
+    ///
+    /// # Arguments
+    /// - `data`: The COLORMAP lump data.
+    /// # Returns
+    /// - `Ok(Colormap)` if the lump holds at least the 32 light level tables.
+    /// - `Err` if the lump is too short.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        if data.len() < LIGHT_TABLES * COLORMAP_TABLE_SIZE {
+            return Err("Colormap data too short".into());
+        }
+
+        Ok(Self { data })
+    }
+
+    /// Number of complete tables in the lump
+    pub fn count(&self) -> usize {
+        self.data.len() / COLORMAP_TABLE_SIZE
+    }
+
+    pub fn table(&self, table: usize) -> Option<&'a [u8]> {
+        if table >= self.count() {
+            return None;
+        }
+
+        let start = table * COLORMAP_TABLE_SIZE;
+        Some(&self.data[start..start + COLORMAP_TABLE_SIZE])
+    }
+
+    pub fn invulnerability(&self) -> Option<&'a [u8]> {
+        self.table(INVULNERABILITY_TABLE)
+    }
+
+    /// Maps a single palette index through a table
+    pub fn shade(&self, index: u8, table: usize) -> Option<u8> {
+        self.table(table).map(|table| table[index as usize])
+    }
+
+    /// Maps palette indices through a table in place, e.g. a decoded flat or patch column
+    pub fn shade_indices(&self, pixels: &mut [u8], table: usize) -> Result<()> {
+        let table = self.table(table).ok_or("colormap table out of range")?;
+        for pixel in pixels {
+            *pixel = table[*pixel as usize];
+        }
+
+        Ok(())
+    }
+
+    /// Builds a palette which already applies a table.
+    ///
+    /// Any decoder that takes a `Palette` (sprites, mip textures, ...) draws a lit picture
+    /// when it is given the shaded palette instead of the full bright one.
+    ///
+    /// # Arguments
+    /// - `palette`: The full bright palette.
+    /// - `table`: The table to apply, usually the result of `light_table`.
+    /// # Returns
+    /// - `Ok(Palette)` with the shaded colors.
+    /// - `Err` if the table does not exist.
+    pub fn shaded_palette(&self, palette: &Palette, table: usize) -> Result<Palette<'static>> {
+        let table = self.table(table).ok_or("colormap table out of range")?;
+        let mut colors = [[0u8; 3]; 256];
+        for (color, &index) in colors.iter_mut().zip(table) {
+            *color = *palette
+                .get_rgb(index as usize)
+                .ok_or("palette index out of bounds")?;
+        }
+
+        Ok(Palette::from_colors(colors))
+    }
+
+    /// Builds the palette for a sector light level and distance, see `light_table`
+    pub fn lit_palette(
+        &self,
+        palette: &Palette,
+        light_level: u8,
+        distance: u32,
+    ) -> Result<Palette<'static>> {
+        self.shaded_palette(palette, light_table(light_level, distance))
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Colormap<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        Colormap::from_bytes(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table n maps every index to `index / (n + 1)`, table 32 inverts the index
+    fn colormap_lump() -> Vec<u8> {
+        let mut data = Vec::new();
+        for table in 0..LIGHT_TABLES {
+            data.extend((0..256).map(|index| (index / (table + 1)) as u8));
+        }
+        data.extend((0..256).map(|index| 255 - index as u8));
+        data.extend([0u8; COLORMAP_TABLE_SIZE]);
+        data
+    }
+
+    #[test]
+    fn colormap_rejects_too_short_data() {
+        assert!(Colormap::from_bytes(&[0u8; 1000]).is_err());
+    }
+
+    #[test]
+    fn colormap_can_shade_indices() {
+        let data = colormap_lump();
+        let colormap = Colormap::from_bytes(&data).unwrap();
+        assert_eq!(colormap.count(), 34);
+        assert_eq!(colormap.shade(200, 0), Some(200));
+        assert_eq!(colormap.shade(200, 1), Some(100));
+        assert_eq!(colormap.invulnerability().unwrap()[0], 255);
+        assert!(colormap.table(34).is_none());
+
+        let mut pixels = [10, 20, 30];
+        colormap.shade_indices(&mut pixels, 9).unwrap();
+        assert_eq!(pixels, [1, 2, 3]);
+        assert!(
+            colormap
+                .shade_indices(&mut pixels, BLACK_TABLE + 1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn colormap_can_build_shaded_palette() {
+        let data = colormap_lump();
+        let colormap = Colormap::from_bytes(&data).unwrap();
+        let palette_data: Vec<u8> = (0..768).map(|val: u16| (val / 3) as u8).collect();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+
+        let shaded = colormap.shaded_palette(&palette, 1).unwrap();
+        assert_eq!(shaded.get_rgb(200), Some(&[100, 100, 100]));
+        let black = colormap.shaded_palette(&palette, BLACK_TABLE).unwrap();
+        assert_eq!(black.get_rgb(200), Some(&[0, 0, 0]));
+    }
+
+    #[test]
+    fn light_table_follows_vanilla_distance_fading() {
+        assert_eq!(light_table(255, 0), 0);
+        assert_eq!(light_table(255, 5000), 0);
+        // close surfaces are drawn brighter than the sector light
+        assert_eq!(light_table(128, 0), 0);
+        assert_eq!(light_table(128, 4096), 28);
+        assert_eq!(light_table(0, 4096), 31);
+        assert!(light_table(128, 64) < light_table(128, 512));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::audio::SoundSample;
+use crate::header::MagicString;
+use crate::index::{LumpNode, LumpSource};
+use crate::pk3::Pk3Writer;
+use crate::resource::NAMESPACE_FOLDERS;
+use crate::sprite::Sprite;
+use crate::wad::WadWriter;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Doubled namespace markers used by PWADs to extend the namespaces of the IWAD
+const MARKER_ALIASES: &[(&str, &str)] = &[
+    ("SS_START", "sprites"),
+    ("FF_START", "flats"),
+    ("PP_START", "patches"),
+];
+
+/// Converts the lump tree of a WAD into a PK3 archive with the conventional folder layout.
+///
+/// # Layout
+/// - Namespaces go into the folders of `NAMESPACE_FOLDERS` (`S_START` to `sprites/`,
+///   `F_START` to `flats/`, ...). Nested namespaces like `F1_START` become sub folders,
+///   unknown namespaces use their lower cased marker prefix as folder name.
+/// - Each map is written as an embedded PWAD `maps/NAME.wad` with its lumps in the order
+///   the engine expects them.
+/// - Top level lumps are sorted by their content: digital and PC speaker sounds go to
+///   `sounds/`, MUS and MIDI lumps to `music/`, patches to `graphics/`, text lumps stay
+///   in the root as `NAME.txt` and everything else in the root as `NAME.lmp`.
+///
+/// File names are lower cased and '\' is replaced by '^', so reading the archive with
+/// `Pk3Archive` produces the original lump names again. Compressed lumps are written
+/// decompressed.
+///
+/// # Arguments
+/// - `source`: The lump tree to convert, usually a `WadIndex`.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the complete PK3 archive.
+/// - `Err` if a lump can't be decompressed or the archive gets too large.
+pub fn wad_to_pk3<'a, S: LumpSource<'a>>(source: &S) -> Result<Vec<u8>> {
+    let mut writer = Pk3Writer::new();
+
+    for (name, node) in sorted(source.get_lump_index()) {
+        match node {
+            LumpNode::Namespace { children, .. } if name == "MAPS" => {
+                for (map, node) in sorted(children) {
+                    if let LumpNode::Namespace { children, .. } = node {
+                        let path = format!("maps/{}.wad", file_name(map));
+                        writer.add_file(&path, &map_wad(map, children)?)?;
+                    }
+                }
+            }
+            LumpNode::Namespace { children, .. } => {
+                add_namespace(&mut writer, &namespace_folder(name), children)?;
+            }
+            LumpNode::Lump { lump, .. } => {
+                let data = lump.decompressed()?;
+                writer.add_file(&top_level_path(name, &data), &data)?;
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Writes the lumps of a namespace into `folder`, nested namespaces become sub folders
+fn add_namespace(
+    writer: &mut Pk3Writer,
+    folder: &str,
+    children: &HashMap<Cow<str>, LumpNode>,
+) -> Result<()> {
+    for (name, node) in sorted(children) {
+        match node {
+            LumpNode::Namespace { children, .. } => {
+                let prefix = name.strip_suffix("_START").unwrap_or(name);
+                let folder = format!("{}/{}", folder, file_name(prefix));
+                add_namespace(writer, &folder, children)?;
+            }
+            LumpNode::Lump { lump, .. } => {
+                let path = format!("{}/{}.lmp", folder, file_name(name));
+                writer.add_file(&path, &lump.decompressed()?)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a PWAD with the map marker followed by all lumps of the map in their original order
+fn map_wad(map: &str, children: &HashMap<Cow<str>, LumpNode>) -> Result<Vec<u8>> {
+    let mut lumps: Vec<_> = children
+        .iter()
+        .filter_map(|(name, node)| match node {
+            LumpNode::Lump { lump, .. } => Some((name.as_ref(), lump)),
+            LumpNode::Namespace { .. } => None,
+        })
+        .collect();
+    lumps.sort_by_key(|(_, lump)| lump.entry_index());
+
+    let mut wad = WadWriter::new(MagicString::PWAD)?;
+    wad.add_marker(map)?;
+    for (name, lump) in lumps {
+        wad.add_lump(name, lump.decompressed()?)?;
+    }
+
+    wad.finish()
+}
+
+fn namespace_folder(marker: &str) -> String {
+    NAMESPACE_FOLDERS
+        .iter()
+        .find(|(_, namespace)| *namespace == Some(marker))
+        .map(|(folder, _)| *folder)
+        .or_else(|| {
+            MARKER_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == marker)
+                .map(|(_, folder)| *folder)
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| file_name(marker.strip_suffix("_START").unwrap_or(marker)))
+}
+
+fn top_level_path(name: &str, data: &[u8]) -> String {
+    let file = file_name(name);
+    if data.starts_with(b"MUS\x1a") {
+        format!("music/{}.mus", file)
+    } else if data.starts_with(b"MThd") {
+        format!("music/{}.mid", file)
+    } else if SoundSample::from_bytes(data).is_ok()
+        || (name.starts_with("DP") && data.starts_with(&[0, 0]))
+    {
+        format!("sounds/{}.lmp", file)
+    } else if is_text(data) {
+        format!("{}.txt", file)
+    } else if Sprite::new(data).is_ok_and(|sprite| sprite.is_valid()) {
+        format!("graphics/{}.lmp", file)
+    } else {
+        format!("{}.lmp", file)
+    }
+}
+
+/// Text lumps (MAPINFO, DECORATE, ...) are valid UTF-8 without control characters
+fn is_text(data: &[u8]) -> bool {
+    !data.is_empty()
+        && std::str::from_utf8(data).is_ok_and(|text| {
+            text.chars()
+                .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x1a'))
+        })
+}
+
+/// Lump names may contain '\', which is not allowed in file names
+fn file_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('\\', "^")
+}
+
+/// HashMap order is random, sorting keeps the archive layout deterministic
+fn sorted<'m, 'a>(
+    nodes: &'m HashMap<Cow<'a, str>, LumpNode<'a>>,
+) -> Vec<(&'m str, &'m LumpNode<'a>)> {
+    let mut nodes: Vec<_> = nodes
+        .iter()
+        .map(|(name, node)| (name.as_ref(), node))
+        .collect();
+    nodes.sort_by_key(|(name, _)| *name);
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+    use crate::pk3::Pk3Archive;
+    use crate::tokenizer::{LumpToken, TokenIterator};
+    use crate::wad::WadIndex;
+
+    /// A valid 1x1 patch
+    const PATCH: [u8; 18] = [1, 0, 1, 0, 0, 0, 0, 0, 12, 0, 0, 0, 0, 1, 0, 5, 0, 0xFF];
+
+    fn test_wad() -> Vec<u8> {
+        let sound = [3, 0, 0x11, 0x2B, 2, 0, 0, 0, 128, 128];
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_lump("PLAYPAL", &[0u8; 768][..]).unwrap();
+        wad.add_lump("MAPINFO", &b"map MAP01 \"Entryway\"\n"[..])
+            .unwrap();
+        wad.add_lump("DSPISTOL", &sound[..]).unwrap();
+        wad.add_lump("D_RUNNIN", &b"MUS\x1a\0\0"[..]).unwrap();
+        wad.add_lump("TITLEPIC", &PATCH[..]).unwrap();
+        wad.add_marker("MAP01").unwrap();
+        wad.add_lump("THINGS", &[1u8, 2][..]).unwrap();
+        wad.add_lump("LINEDEFS", &[3u8, 4][..]).unwrap();
+        wad.add_marker("S_START").unwrap();
+        wad.add_lump("VILEA\\D", &PATCH[..]).unwrap();
+        wad.add_marker("S_END").unwrap();
+        wad.add_marker("F_START").unwrap();
+        wad.add_marker("F1_START").unwrap();
+        wad.add_lump("FLOOR0_1", &[7u8; 4096][..]).unwrap();
+        wad.add_marker("F1_END").unwrap();
+        wad.add_marker("F_END").unwrap();
+        wad.finish().unwrap()
+    }
+
+    #[test]
+    fn wad_to_pk3_uses_conventional_folders() {
+        let data = test_wad();
+        let wad = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let pk3 = wad_to_pk3(&wad).unwrap();
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &pk3).unwrap();
+
+        let paths: Vec<&str> = archive.files().iter().map(|file| file.path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "sounds/dspistol.lmp",
+                "music/d_runnin.mus",
+                "flats/f1/floor0_1.lmp",
+                "mapinfo.txt",
+                "maps/map01.wad",
+                "playpal.lmp",
+                "sprites/vilea^d.lmp",
+                "graphics/titlepic.lmp",
+            ]
+        );
+    }
+
+    #[test]
+    fn wad_to_pk3_round_trips_through_pk3_archive() {
+        let data = test_wad();
+        let wad = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let pk3 = wad_to_pk3(&wad).unwrap();
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &pk3).unwrap();
+        let index = archive.index().unwrap();
+
+        for name in ["PLAYPAL", "MAPINFO", "DSPISTOL", "D_RUNNIN", "TITLEPIC"] {
+            assert!(index.get_lump(vec![], name).is_some(), "{} missing", name);
+        }
+        assert!(index.get_lump(vec!["S_START"], "VILEA\\D").is_some());
+        assert!(index.get_lump(vec!["F_START"], "FLOOR0_1").is_some());
+        match index.get_lump(vec!["MAPS", "MAP01"], "LINEDEFS").unwrap() {
+            LumpNode::Lump { lump, .. } => assert_eq!(lump.data(), &[3, 4]),
+            _ => panic!("LINEDEFS is not a lump"),
+        }
+    }
+
+    #[test]
+    fn map_wads_keep_every_map_lump_in_order() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_marker("MAP01").unwrap();
+        wad.add_lump("TEXTMAP", &b"namespace = \"zdoom\";"[..])
+            .unwrap();
+        wad.add_lump("ZNODES", &[1u8][..]).unwrap();
+        wad.add_lump("SCRIPTS", &[2u8][..]).unwrap();
+        wad.add_marker("ENDMAP").unwrap();
+        wad.add_marker("MAP02").unwrap();
+        wad.add_lump("THINGS", &[3u8][..]).unwrap();
+        wad.add_lump("LINEDEFS", &[4u8][..]).unwrap();
+        wad.add_lump("BEHAVIOR", &[5u8][..]).unwrap();
+        wad.add_marker("GL_MAP02").unwrap();
+        wad.add_lump("GL_VERT", &[6u8][..]).unwrap();
+        wad.add_lump("DEHACKED", &b"Patch File for DeHackEd"[..])
+            .unwrap();
+        let data = wad.finish().unwrap();
+        let wad = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+
+        let pk3 = wad_to_pk3(&wad).unwrap();
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &pk3).unwrap();
+        let names = |path: &str| -> Vec<String> {
+            let file = archive.files().iter().find(|f| f.path() == path).unwrap();
+            let header = Header::try_from(<&[u8; 12]>::try_from(&file.data()[..12]).unwrap());
+            TokenIterator::new(header.unwrap(), file.data())
+                .unwrap()
+                .map(|token| match token.unwrap() {
+                    LumpToken::MapMarker(name) => name.to_string(),
+                    LumpToken::Lump(name, _) => name.into_owned(),
+                    _ => panic!("unexpected marker"),
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            names("maps/map01.wad"),
+            ["MAP01", "TEXTMAP", "ZNODES", "SCRIPTS", "ENDMAP"]
+        );
+        assert_eq!(
+            names("maps/map02.wad"),
+            [
+                "MAP02", "THINGS", "LINEDEFS", "BEHAVIOR", "GL_MAP02", "GL_VERT"
+            ]
+        );
+        assert!(archive.files().iter().any(|f| f.path() == "dehacked.txt"));
+    }
+}
@@ -0,0 +1,328 @@
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Lumps which hold the text screen shown when the game exits: ENDOOM (Doom),
+/// ENDTEXT (Heretic) and ENDSTRF (Strife)
+pub const TEXT_SCREEN_LUMPS: &[&str] = &["ENDOOM", "ENDTEXT", "ENDSTRF"];
+
+/// Columns of the text screen
+pub const TEXT_SCREEN_WIDTH: usize = 80;
+/// Rows of the text screen
+pub const TEXT_SCREEN_HEIGHT: usize = 25;
+/// Size of a text screen lump, two bytes per cell
+pub const TEXT_SCREEN_SIZE: usize = TEXT_SCREEN_WIDTH * TEXT_SCREEN_HEIGHT * 2;
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: usize = 8;
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: usize = 16;
+/// Size of an 8x16 font with 256 glyphs, one byte per glyph row
+pub const FONT_SIZE: usize = 256 * GLYPH_HEIGHT;
+
+/// The 16 colors of the VGA text mode
+pub const VGA_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xAA],
+    [0x00, 0xAA, 0x00],
+    [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00],
+    [0xAA, 0x00, 0xAA],
+    [0xAA, 0x55, 0x00],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xFF],
+    [0x55, 0xFF, 0x55],
+    [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55],
+    [0xFF, 0x55, 0xFF],
+    [0xFF, 0xFF, 0x55],
+    [0xFF, 0xFF, 0xFF],
+];
+
+/// ANSI color numbers of the 8 base VGA colors, the order of red and blue differs
+const ANSI_COLORS: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+/// Unicode characters of the CP437 code page, 0x00 is shown as a space
+const CP437_LOW: &str = " ☺☻♥♦♣♠•◘○◙♂♀♪♫☼►◄↕‼¶§▬↨↑↓→←∟↔▲▼";
+const CP437_HIGH: &str = "⌂ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+    ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+    αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{A0}";
+
+/// Converts a CP437 character into Unicode
+pub fn cp437_to_char(character: u8) -> char {
+    match character {
+        0x00..=0x1F => CP437_LOW.chars().nth(character as usize),
+        0x20..=0x7E => Some(character as char),
+        _ => CP437_HIGH.chars().nth(character as usize - 0x7F),
+    }
+    .unwrap_or(' ')
+}
+
+/// A single character cell of a text screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextCell {
+    /// The CP437 character
+    pub character: u8,
+    /// Foreground color, an index into `VGA_COLORS` (0-15)
+    pub foreground: u8,
+    /// Background color, an index into `VGA_COLORS` (0-7)
+    pub background: u8,
+    pub blink: bool,
+}
+
+impl TextCell {
+    /// Decodes a cell from the character and the attribute byte.
+    ///
+    /// The attribute holds the foreground color in bits 0-3, the background color
+    /// in bits 4-6 and the blink flag in bit 7.
+    pub fn new(character: u8, attribute: u8) -> Self {
+        Self {
+            character,
+            foreground: attribute & 0x0F,
+            background: (attribute >> 4) & 0x07,
+            blink: attribute & 0x80 != 0,
+        }
+    }
+
+    /// The character as Unicode
+    pub fn to_char(&self) -> char {
+        cp437_to_char(self.character)
+    }
+}
+
+/// Represents an ENDOOM like text screen lump.
+///
+/// # Structure of a Text Screen Lump
+/// The lump is a copy of the VGA text mode buffer, 80x25 cells row by row. Every cell
+/// takes two bytes:
+/// - 1 byte: The CP437 character
+/// - 1 byte: The color attribute, see `TextCell::new`
+#[derive(Debug, Clone)]
+pub struct TextScreen {
+    cells: Vec<TextCell>,
+}
+
+impl TextScreen {
+    /// Creates a `TextScreen` from the lump data.
+    ///
+    /// # Arguments
+    /// - `data`: The ENDOOM, ENDTEXT or ENDSTRF lump data.
+    /// # Returns
+    /// - `Ok(TextScreen)` with the 2000 cells of the screen.
+    /// - `Err` if the lump is shorter than 4000 bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let data = data
+            .get(..TEXT_SCREEN_SIZE)
+            .ok_or("Text screen data too short")?;
+        let cells = data
+            .chunks_exact(2)
+            .map(|cell| TextCell::new(cell[0], cell[1]))
+            .collect();
+
+        Ok(Self { cells })
+    }
+
+    /// The cells row by row
+    pub fn cells(&self) -> &[TextCell] {
+        &self.cells
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<TextCell> {
+        if x >= TEXT_SCREEN_WIDTH || y >= TEXT_SCREEN_HEIGHT {
+            return None;
+        }
+
+        Some(self.cells[y * TEXT_SCREEN_WIDTH + x])
+    }
+
+    /// The rows of the screen
+    pub fn rows(&self) -> impl Iterator<Item = &[TextCell]> {
+        self.cells.chunks_exact(TEXT_SCREEN_WIDTH)
+    }
+
+    /// Converts the screen into UTF-8 text without colors, one line per row
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity(TEXT_SCREEN_SIZE);
+        for row in self.rows() {
+            text.extend(row.iter().map(TextCell::to_char));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Converts the screen into UTF-8 text with ANSI color escapes for terminals.
+    ///
+    /// Bright foreground colors use the 90-97 codes, blinking cells use the blink code 5.
+    /// Every line ends with a reset, so the output can be printed line by line.
+    pub fn to_ansi(&self) -> String {
+        let mut text = String::with_capacity(TEXT_SCREEN_SIZE * 4);
+        for row in self.rows() {
+            let mut current: Option<(u8, u8, bool)> = None;
+            for cell in row {
+                let attributes = (cell.foreground, cell.background, cell.blink);
+                if current != Some(attributes) {
+                    let foreground = ANSI_COLORS[(cell.foreground & 0x07) as usize];
+                    let foreground = match cell.foreground {
+                        0..=7 => 30 + foreground,
+                        _ => 90 + foreground,
+                    };
+                    let background = 40 + ANSI_COLORS[cell.background as usize];
+                    let blink = if cell.blink { 5 } else { 25 };
+                    text.push_str(&format!("\x1b[{foreground};{background};{blink}m"));
+                    current = Some(attributes);
+                }
+                text.push(cell.to_char());
+            }
+            text.push_str("\x1b[0m\n");
+        }
+
+        text
+    }
+
+    /// Renders the screen into an RGBA pixel buffer of 640x400 pixels with the 8x16 VGA font,
+    /// like the text mode the game exits to. Blinking characters are drawn visible.
+    pub fn rgba_pixel_buffer(&self) -> Vec<u8> {
+        self.render(VGA_FONT)
+    }
+
+    /// Renders the screen into an RGBA pixel buffer of 640x400 pixels with an 8x16 font.
+    ///
+    /// # Arguments
+    /// - `font`: 256 glyphs of 16 bytes, one byte per row with the leftmost pixel
+    ///   in the highest bit, the layout of VGA font dumps.
+    /// # Returns
+    /// - `Ok(Vec<u8>)` with the rendered screen.
+    /// - `Err` if the font is shorter than 4096 bytes.
+    pub fn rgba_pixel_buffer_with_font(&self, font: &[u8]) -> Result<Vec<u8>> {
+        let font = font.get(..FONT_SIZE).ok_or("Font data too short")?;
+        Ok(self.render(font))
+    }
+
+    fn render(&self, font: &[u8]) -> Vec<u8> {
+        let width = TEXT_SCREEN_WIDTH * GLYPH_WIDTH;
+        let mut pixel_buffer = vec![0u8; width * TEXT_SCREEN_HEIGHT * GLYPH_HEIGHT * 4];
+        for (i, cell) in self.cells.iter().enumerate() {
+            let cell_x = (i % TEXT_SCREEN_WIDTH) * GLYPH_WIDTH;
+            let cell_y = (i / TEXT_SCREEN_WIDTH) * GLYPH_HEIGHT;
+            let glyph = &font[cell.character as usize * GLYPH_HEIGHT..][..GLYPH_HEIGHT];
+            for (dy, &bits) in glyph.iter().enumerate() {
+                for dx in 0..GLYPH_WIDTH {
+                    let color = if bits & (0x80 >> dx) != 0 {
+                        cell.foreground
+                    } else {
+                        cell.background
+                    };
+                    let [r, g, b] = VGA_COLORS[color as usize];
+                    let pos = ((cell_y + dy) * width + cell_x + dx) * 4;
+                    pixel_buffer[pos..pos + 4].copy_from_slice(&[r, g, b, 255]);
+                }
+            }
+        }
+
+        pixel_buffer
+    }
+}
+
+impl TryFrom<&[u8]> for TextScreen {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        TextScreen::from_bytes(value)
+    }
+}
+
+/// The 8x16 font of the VGA BIOS with all 256 CP437 glyphs, one byte per glyph row with
+/// the leftmost pixel in the highest bit
+static VGA_FONT: &[u8; FONT_SIZE] = include_bytes!("../assets/fonts/vga8x16.bin");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_screen_lump() -> Vec<u8> {
+        let mut data = Vec::with_capacity(TEXT_SCREEN_SIZE);
+        for _ in 0..TEXT_SCREEN_WIDTH * TEXT_SCREEN_HEIGHT {
+            data.extend(&[b' ', 0x07]);
+        }
+        // "Hi" in bright yellow on blue, blinking, followed by a box corner
+        data[0..6].copy_from_slice(&[b'H', 0x9E, b'i', 0x9E, 0xC9, 0x1F]);
+        data
+    }
+
+    #[test]
+    fn text_screen_decodes_cells() {
+        assert!(TextScreen::from_bytes(&[0u8; 3998]).is_err());
+
+        let screen = TextScreen::from_bytes(&text_screen_lump()).unwrap();
+        let cell = screen.get(0, 0).unwrap();
+        assert_eq!(
+            cell,
+            TextCell {
+                character: b'H',
+                foreground: 14,
+                background: 1,
+                blink: true
+            }
+        );
+        assert_eq!(screen.get(2, 0).unwrap().to_char(), '╔');
+        assert!(screen.get(80, 0).is_none());
+        assert_eq!(cp437_to_char(0x01), '☺');
+        assert_eq!(cp437_to_char(0xE1), 'ß');
+        assert_eq!(cp437_to_char(0xFF), '\u{A0}');
+
+        let text = screen.to_text();
+        assert_eq!(text.lines().count(), TEXT_SCREEN_HEIGHT);
+        assert!(text.starts_with("Hi╔ "));
+    }
+
+    #[test]
+    fn text_screen_converts_to_ansi() {
+        let screen = TextScreen::from_bytes(&text_screen_lump()).unwrap();
+        let ansi = screen.to_ansi();
+        let first_line = ansi.lines().next().unwrap();
+        assert!(first_line.starts_with("\x1b[93;44;5mHi\x1b[97;44;25m╔\x1b[37;40;25m "));
+        assert!(first_line.ends_with("\x1b[0m"));
+    }
+
+    #[test]
+    fn text_screen_renders_with_font() {
+        let screen = TextScreen::from_bytes(&text_screen_lump()).unwrap();
+        let rgba = screen.rgba_pixel_buffer();
+        assert_eq!(rgba.len(), 640 * 400 * 4);
+        // the left stem of 'H' starts in the third row, the rows above and the bottom row
+        // of the cell are background blue
+        assert_eq!(&rgba[0..4], &[0x00, 0x00, 0xAA, 255]);
+        let pos = (2 * 640) * 4;
+        assert_eq!(&rgba[pos..pos + 4], &[0xFF, 0xFF, 0x55, 255]);
+        let pos = (15 * 640) * 4;
+        assert_eq!(&rgba[pos..pos + 4], &[0x00, 0x00, 0xAA, 255]);
+
+        let font = vec![0xFFu8; FONT_SIZE];
+        let rgba = screen.rgba_pixel_buffer_with_font(&font).unwrap();
+        assert_eq!(&rgba[pos..pos + 4], &[0xFF, 0xFF, 0x55, 255]);
+        assert!(screen.rgba_pixel_buffer_with_font(&font[1..]).is_err());
+    }
+
+    #[test]
+    fn vga_font_has_cp437_glyphs() {
+        let glyph = |character: usize| &VGA_FONT[character * GLYPH_HEIGHT..][..GLYPH_HEIGHT];
+        // full block
+        assert!(glyph(0xDB).iter().all(|&row| row == 0xFF));
+        // single horizontal line through row 7
+        assert_eq!(glyph(0xC4)[7], 0xFF);
+        assert_eq!(glyph(0xC4).iter().filter(|&&row| row != 0).count(), 1);
+        // single vertical line, and the double top left corner
+        assert!(glyph(0xB3).iter().all(|&row| row == 0x18));
+        assert_eq!(&glyph(0xC9)[4..9], &[0x00, 0x3F, 0x30, 0x37, 0x36]);
+        // lower half block and the medium shade
+        assert_eq!(&glyph(0xDC)[6..8], &[0x00, 0xFF]);
+        assert_eq!(&glyph(0xB1)[..2], &[0x55, 0xAA]);
+        // 'A' and 'é'
+        assert_eq!(
+            &glyph(b'A' as usize)[2..8],
+            &[0x10, 0x38, 0x6C, 0xC6, 0xC6, 0xFE]
+        );
+        assert_eq!(&glyph(0x82)[1..4], &[0x0C, 0x18, 0x30]);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::graphics::{IndexedImage, Palette};
+use crate::picture::Picture;
+use crate::png::PNG_SIGNATURE;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+const PNG_COLOR_PALETTE: u8 = 3;
+const PNG_COLOR_RGBA: u8 = 6;
+/// zlib compression level of the PNG image data
+const PNG_COMPRESSION_LEVEL: u8 = 6;
+
+/// Size of the BMP file header
+const BMP_FILE_HEADER_SIZE: usize = 14;
+/// Size of the BITMAPV4HEADER, the first info header with an alpha mask
+const BMP_V4_HEADER_SIZE: usize = 108;
+/// 72 DPI in pixels per meter
+const BMP_RESOLUTION: u32 = 2835;
+/// BI_BITFIELDS compression, the pixel layout is given by the color masks
+const BMP_BITFIELDS: u32 = 3;
+/// The `sRGB` color space tag
+const BMP_SRGB: u32 = 0x7352_4742;
+
+/// Uncompressed true color TGA image
+const TGA_TRUE_COLOR: u8 = 2;
+/// 8 alpha bits with the origin at the top left
+const TGA_DESCRIPTOR: u8 = 0x28;
+
+/// Writes an RGBA image as a PNG file.
+///
+/// # Arguments
+/// - `width`, `height`: The size of the image.
+/// - `rgba`: The image, `width * height * 4` bytes row by row.
+/// - `offsets`: Patch offsets, written as grAb chunk like SLADE and ZDoom expect.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the PNG file.
+/// - `Err` if the buffer size does not match.
+pub fn encode_png_rgba(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    offsets: Option<(i32, i32)>,
+) -> Result<Vec<u8>> {
+    check_rgba_size(width, height, rgba)?;
+
+    let mut png = PngWriter::new(width, height, PNG_COLOR_RGBA, offsets);
+    png.image_data(rgba, width as usize * 4);
+    Ok(png.finish())
+}
+
+/// Writes an indexed image as a paletted PNG file with the palette as PLTE chunk.
+///
+/// Transparent pixels use a palette index no opaque pixel uses, which is marked
+/// transparent in a tRNS chunk, so the opaque pixels keep their exact palette indices.
+/// If an image with transparent pixels uses all 256 indices, there is no index left and
+/// the image is written as RGBA PNG instead.
+///
+/// # Arguments
+/// - `image`: The palette indices and transparency mask.
+/// - `palette`: The palette written to the PLTE chunk.
+/// - `offsets`: Patch offsets, written as grAb chunk.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the PNG file.
+/// - `Err` if the image is larger than a PNG can hold.
+pub fn encode_png_indexed(
+    image: &IndexedImage,
+    palette: &Palette,
+    offsets: Option<(i32, i32)>,
+) -> Result<Vec<u8>> {
+    let width = u32::try_from(image.width())?;
+    let height = u32::try_from(image.height())?;
+
+    let mut used = [false; 256];
+    for (&index, &opaque) in image.pixels().iter().zip(image.mask()) {
+        if opaque {
+            used[index as usize] = true;
+        }
+    }
+
+    let has_transparency = image.mask().iter().any(|&opaque| !opaque);
+    let transparent_index = used.iter().position(|&used| !used);
+    if has_transparency && transparent_index.is_none() {
+        return encode_png_rgba(width, height, &image.rgba_pixel_buffer(palette)?, offsets);
+    }
+
+    let mut plte = Vec::with_capacity(256 * 3);
+    for index in 0..256 {
+        plte.extend(
+            palette
+                .get_rgb(index)
+                .ok_or("palette index out of bounds")?,
+        );
+    }
+
+    let mut pixels = image.pixels().to_vec();
+    let mut png = PngWriter::new(width, height, PNG_COLOR_PALETTE, offsets);
+    png.chunk(b"PLTE", &plte);
+    if let Some(transparent) = transparent_index.filter(|_| has_transparency) {
+        let mut trns = vec![255u8; transparent + 1];
+        trns[transparent] = 0;
+        png.chunk(b"tRNS", &trns);
+
+        for (pixel, &opaque) in pixels.iter_mut().zip(image.mask()) {
+            if !opaque {
+                *pixel = transparent as u8;
+            }
+        }
+    }
+    png.image_data(&pixels, width as usize);
+
+    Ok(png.finish())
+}
+
+/// Writes a decoded picture as a PNG file.
+///
+/// Doom formats are written as paletted PNG with the palette, patches keep their offsets
+/// in a grAb chunk. PNG lumps are written as RGBA with their own offsets.
+pub fn picture_to_png(picture: &Picture, palette: &Palette) -> Result<Vec<u8>> {
+    let (width, height) = (picture.width() as usize, picture.height() as usize);
+    let image = match picture {
+        Picture::Patch(patch) => patch.indexed_image()?,
+        Picture::Flat(flat) => opaque_image(width, height, flat.indexed_pixels()),
+        Picture::Raw(raw) => opaque_image(width, height, raw.indexed_pixels()),
+        Picture::Png(png) => {
+            return encode_png_rgba(png.width(), png.height(), png.rgba(), png.offsets());
+        }
+    };
+
+    let offsets = match picture {
+        Picture::Patch(_) => Some((picture.left_offset(), picture.top_offset())),
+        _ => None,
+    };
+    encode_png_indexed(&image, palette, offsets)
+}
+
+/// Writes an RGBA image as a 32 bit BMP file with alpha channel.
+///
+/// # Arguments
+/// - `width`, `height`: The size of the image.
+/// - `rgba`: The image, `width * height * 4` bytes row by row.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the BMP file.
+/// - `Err` if the buffer size does not match or the file would exceed 4 GiB.
+pub fn encode_bmp(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+    check_rgba_size(width, height, rgba)?;
+
+    let header_size = BMP_FILE_HEADER_SIZE + BMP_V4_HEADER_SIZE;
+    let image_size = u32::try_from(rgba.len())?;
+    let file_size = image_size
+        .checked_add(header_size as u32)
+        .ok_or("image too large for BMP")?;
+
+    let mut bmp = Vec::with_capacity(file_size as usize);
+    bmp.extend(b"BM");
+    bmp.extend(&file_size.to_le_bytes());
+    bmp.extend(&[0u8; 4]);
+    bmp.extend(&(header_size as u32).to_le_bytes());
+
+    bmp.extend(&(BMP_V4_HEADER_SIZE as u32).to_le_bytes());
+    bmp.extend(&i32::try_from(width)?.to_le_bytes());
+    // a positive height stores the rows bottom up
+    bmp.extend(&i32::try_from(height)?.to_le_bytes());
+    bmp.extend(&1u16.to_le_bytes());
+    bmp.extend(&32u16.to_le_bytes());
+    bmp.extend(&BMP_BITFIELDS.to_le_bytes());
+    bmp.extend(&image_size.to_le_bytes());
+    bmp.extend(&BMP_RESOLUTION.to_le_bytes());
+    bmp.extend(&BMP_RESOLUTION.to_le_bytes());
+    bmp.extend(&[0u8; 8]);
+    for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+        bmp.extend(&mask.to_le_bytes());
+    }
+    bmp.extend(&BMP_SRGB.to_le_bytes());
+    // color space endpoints and gamma, unused for sRGB
+    bmp.extend(&[0u8; 48]);
+
+    for row in rgba.chunks_exact((width as usize * 4).max(1)).rev() {
+        for pixel in row.chunks_exact(4) {
+            bmp.extend(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+
+    Ok(bmp)
+}
+
+/// Writes an RGBA image as an uncompressed 32 bit TGA file.
+///
+/// # Arguments
+/// - `width`, `height`: The size of the image, at most 65535 pixels each.
+/// - `rgba`: The image, `width * height * 4` bytes row by row.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the TGA file.
+/// - `Err` if the buffer size does not match or the image is too large.
+pub fn encode_tga(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+    check_rgba_size(width, height, rgba)?;
+
+    let mut tga = Vec::with_capacity(18 + rgba.len());
+    tga.extend(&[0, 0, TGA_TRUE_COLOR]);
+    // no color map, origin at 0, 0
+    tga.extend(&[0u8; 9]);
+    tga.extend(&u16::try_from(width)?.to_le_bytes());
+    tga.extend(&u16::try_from(height)?.to_le_bytes());
+    tga.extend(&[32, TGA_DESCRIPTOR]);
+
+    for pixel in rgba.chunks_exact(4) {
+        tga.extend(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+    }
+
+    Ok(tga)
+}
+
+fn check_rgba_size(width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    if rgba.len() as u64 != width as u64 * height as u64 * 4 {
+        Err("RGBA buffer size doesn't match the image size".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Builds an image without transparency from row major palette indices
+fn opaque_image(width: usize, height: usize, pixels: &[u8]) -> IndexedImage {
+    let mut image = IndexedImage::new(width, height);
+    for (i, &index) in pixels.iter().enumerate() {
+        image.set(i % width, i / width, index);
+    }
+
+    image
+}
+
+/// Assembles the chunks of a PNG file with 8 bit samples
+struct PngWriter {
+    data: Vec<u8>,
+}
+
+impl PngWriter {
+    fn new(width: u32, height: u32, color_type: u8, offsets: Option<(i32, i32)>) -> Self {
+        let mut png = Self {
+            data: PNG_SIGNATURE.to_vec(),
+        };
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend(&width.to_be_bytes());
+        ihdr.extend(&height.to_be_bytes());
+        // bit depth, color type, compression, filter and interlace method
+        ihdr.extend(&[8, color_type, 0, 0, 0]);
+        png.chunk(b"IHDR", &ihdr);
+
+        if let Some((x, y)) = offsets {
+            let mut grab = x.to_be_bytes().to_vec();
+            grab.extend(&y.to_be_bytes());
+            png.chunk(b"grAb", &grab);
+        }
+
+        png
+    }
+
+    fn chunk(&mut self, chunk_type: &[u8; 4], content: &[u8]) {
+        self.data.extend(&(content.len() as u32).to_be_bytes());
+        let start = self.data.len();
+        self.data.extend(chunk_type);
+        self.data.extend(content);
+        let crc = crc32fast::hash(&self.data[start..]);
+        self.data.extend(&crc.to_be_bytes());
+    }
+
+    /// Writes the rows without filter as compressed IDAT chunk
+    fn image_data(&mut self, pixels: &[u8], stride: usize) {
+        let mut raw = Vec::with_capacity(pixels.len() + pixels.len() / stride.max(1));
+        for row in pixels.chunks(stride.max(1)) {
+            raw.push(0);
+            raw.extend(row);
+        }
+
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&raw, PNG_COMPRESSION_LEVEL);
+        self.chunk(b"IDAT", &compressed);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.chunk(b"IEND", &[]);
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::PngImage;
+    use crate::sprite::Sprite;
+
+    /// Palette where color n is (n, n, n), except color 1 which duplicates color 0
+    fn palette_data() -> Vec<u8> {
+        let mut data: Vec<u8> = (0..768).map(|val: u16| (val / 3) as u8).collect();
+        data[3..6].fill(0);
+        data
+    }
+
+    #[test]
+    fn indexed_png_round_trips_indices_and_offsets() {
+        // 2x2 patch, column 0 has rows 0-1 with indices 1 and 0, column 1 is empty
+        let mut patch = vec![2, 0, 2, 0, 5, 0, 0xF9, 0xFF];
+        patch.extend(&16u32.to_le_bytes());
+        patch.extend(&23u32.to_le_bytes());
+        patch.extend(&[0, 2, 0, 1, 0, 0, 0xFF, 0xFF]);
+        let palette_data = palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+
+        let picture = Picture::from_patch_lump(&patch).unwrap();
+        let png = picture_to_png(&picture, &palette).unwrap();
+        let decoded = PngImage::from_bytes(&png).unwrap();
+
+        assert_eq!(decoded.offsets(), Some((5, -7)));
+        assert_eq!(decoded.palette().unwrap().len(), 256);
+        let image = decoded.indexed_image(&palette);
+        let original = Sprite::new(&patch).unwrap().indexed_image().unwrap();
+        assert_eq!(image.pixels(), original.pixels());
+        assert_eq!(image.mask(), original.mask());
+    }
+
+    #[test]
+    fn indexed_png_falls_back_to_rgba_without_free_index() {
+        let mut image = IndexedImage::new(257, 1);
+        for x in 0..256 {
+            image.set(x, 0, x as u8);
+        }
+        let palette_data = palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+
+        let png = encode_png_indexed(&image, &palette, None).unwrap();
+        let decoded = PngImage::from_bytes(&png).unwrap();
+        assert!(decoded.palette().is_none());
+        assert_eq!(&decoded.rgba()[256 * 4..], &[0, 0, 0, 0]);
+        assert_eq!(decoded.offsets(), None);
+    }
+
+    #[test]
+    fn rgba_png_bmp_and_tga_can_be_written() {
+        let rgba = [1, 2, 3, 255, 4, 5, 6, 0];
+        let decoded = PngImage::from_bytes(&encode_png_rgba(1, 2, &rgba, None).unwrap()).unwrap();
+        assert_eq!(decoded.rgba(), &rgba);
+        assert!(encode_png_rgba(2, 2, &rgba, None).is_err());
+
+        let bmp = encode_bmp(1, 2, &rgba).unwrap();
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(bmp.len(), 122 + 8);
+        // bottom up rows in BGRA order
+        assert_eq!(&bmp[122..], &[6, 5, 4, 0, 3, 2, 1, 255]);
+
+        let tga = encode_tga(1, 2, &rgba).unwrap();
+        assert_eq!(&tga[12..18], &[1, 0, 2, 0, 32, 0x28]);
+        assert_eq!(&tga[18..], &[3, 2, 1, 255, 6, 5, 4, 0]);
+        assert!(encode_tga(70000, 0, &[]).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::graphics::Palette;
+use crate::index::{LumpNode, LumpSource};
+use crate::lump::LumpRef;
+use crate::rgba::RgbaTable;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Namespaces which hold flats, `FF_START` is used by PWADs to add flats to the IWAD ones
+pub const FLAT_NAMESPACES: &[&str] = &["F_START", "FF_START"];
+
+/// Known flat dimensions by lump size. Some Heretic flats have an extra row of 64 bytes
+/// after the image, the row is ignored.
+const FLAT_SIZES: &[(usize, u16, u16)] = &[
+    (64 * 64, 64, 64),
+    (64 * 65, 64, 64),
+    (64 * 128, 64, 128),
+    (128 * 128, 128, 128),
+    (256 * 256, 256, 256),
+];
+
+/// Represents a flat, the floor and ceiling textures of Doom.
+///
+/// # Structure of a Flat Lump
+/// A flat has no header, it is a raw row major block of palette indices. The dimensions
+/// are derived from the lump size:
+/// - 4096 bytes: 64x64 (vanilla)
+/// - 4160 bytes: 64x64 followed by an unused row (Heretic)
+/// - 8192 bytes: 64x128
+/// - 16384 bytes: 128x128
+/// - 65536 bytes: 256x256
+#[derive(Debug, Clone)]
+pub struct Flat<'a> {
+    pixels: &'a [u8],
+    width: u16,
+    height: u16,
+}
+
+impl<'a> Flat<'a> {
+    /// Creates a `Flat` from the complete lump slice.
+    ///
+    /// # Arguments
+    /// - `lump_data`: The data of the flat lump.
+    /// # Returns
+    /// - `Ok(Flat)` with the dimensions detected from the lump size.
+    /// - `Err` if the lump size is no known flat size.
+    pub fn new(lump_data: &'a [u8]) -> Result<Self> {
+        let &(_, width, height) = FLAT_SIZES
+            .iter()
+            .find(|(size, ..)| lump_data.len() == *size)
+            .ok_or("Lump size does not match a known flat size")?;
+
+        Ok(Self {
+            pixels: &lump_data[..width as usize * height as usize],
+            width,
+            height,
+        })
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The palette indices of the flat, row by row
+    pub fn indexed_pixels(&self) -> &'a [u8] {
+        self.pixels
+    }
+
+    /// Converts the flat into an RGBA pixel buffer with `width * height * 4` bytes.
+    /// Flats are always opaque.
+    pub fn rgba_pixel_buffer(&self, palette: &Palette) -> Result<Vec<u8>> {
+        Ok(RgbaTable::new(palette).convert(self.pixels))
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Flat<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        Flat::new(value)
+    }
+}
+
+/// Collects every flat lump of a lump tree.
+///
+/// Walks the flat namespaces of `FLAT_NAMESPACES` including nested namespaces like
+/// `F1_START`, markers and empty lumps are skipped. The lumps are returned as `LumpRef`,
+/// so compressed flats can be decompressed before they are decoded.
+///
+/// # Returns
+/// - The flats sorted by name, flats of `FF_START` replace `F_START` flats with the same name.
+pub fn flat_lumps<'a, S: LumpSource<'a>>(source: &S) -> Vec<(Cow<'a, str>, LumpRef<'a>)> {
+    let mut flats = HashMap::new();
+    for namespace in FLAT_NAMESPACES {
+        if let Some(LumpNode::Namespace { children, .. }) = source.get_lump(vec![], namespace) {
+            collect_flats(children, &mut flats);
+        }
+    }
+
+    let mut flats: Vec<_> = flats.into_iter().collect();
+    flats.sort_by(|(a, _), (b, _)| a.cmp(b));
+    flats
+}
+
+fn collect_flats<'a>(
+    children: &HashMap<Cow<'a, str>, LumpNode<'a>>,
+    flats: &mut HashMap<Cow<'a, str>, LumpRef<'a>>,
+) {
+    for node in children.values() {
+        match node {
+            LumpNode::Namespace { children, .. } => collect_flats(children, flats),
+            LumpNode::Lump { name, lump } if lump.size() > 0 => {
+                flats.insert(name.clone(), lump.clone());
+            }
+            LumpNode::Lump { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::wad::{WadIndex, WadWriter};
+
+    #[test]
+    fn flat_rejects_unknown_sizes() {
+        assert!(Flat::new(&[0u8; 4000]).is_err());
+        assert!(Flat::new(&[0u8; 5000]).is_err());
+        assert!(Flat::new(&[0u8; 70000]).is_err());
+    }
+
+    #[test]
+    fn flat_detects_size_from_lump_length() {
+        let sizes = [
+            (4096, 64, 64),
+            (4160, 64, 64),
+            (8192, 64, 128),
+            (16384, 128, 128),
+            (65536, 256, 256),
+        ];
+        for (length, width, height) in sizes {
+            let data = vec![0u8; length];
+            let flat = Flat::new(&data).unwrap();
+            assert_eq!((flat.width(), flat.height()), (width, height));
+            assert_eq!(
+                flat.indexed_pixels().len(),
+                width as usize * height as usize
+            );
+        }
+    }
+
+    #[test]
+    fn flat_can_convert_to_rgba() {
+        let data: Vec<u8> = (0..4096).map(|i| (i % 256) as u8).collect();
+        let palette_data: Vec<u8> = (0..768).map(|val: u16| (val % 256) as u8).collect();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let rgba = Flat::new(&data)
+            .unwrap()
+            .rgba_pixel_buffer(&palette)
+            .unwrap();
+        assert_eq!(rgba.len(), 64 * 64 * 4);
+        assert_eq!(&rgba[4..8], &[3, 4, 5, 255]);
+    }
+
+    #[test]
+    fn flat_lumps_walks_nested_flat_namespaces() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_marker("F_START").unwrap();
+        wad.add_marker("F1_START").unwrap();
+        wad.add_lump("FLOOR0_1", vec![1u8; 4096]).unwrap();
+        wad.add_marker("F1_END").unwrap();
+        wad.add_lump("F_SKY1", vec![2u8; 4096]).unwrap();
+        wad.add_marker("F_END").unwrap();
+        wad.add_marker("FF_START").unwrap();
+        wad.add_lump("NUKAGE1", vec![3u8; 4096]).unwrap();
+        wad.add_marker("FF_END").unwrap();
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+
+        let flats = flat_lumps(&index);
+        let names: Vec<&str> = flats.iter().map(|(name, _)| name.as_ref()).collect();
+        assert_eq!(names, vec!["FLOOR0_1", "F_SKY1", "NUKAGE1"]);
+    }
+}
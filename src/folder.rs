@@ -0,0 +1,117 @@
+use crate::resource::{ResourceFile, ResourceIndex};
+use std::fs;
+use std::path::Path;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// A folder tree of loose files used as a virtual WAD during development.
+///
+/// The folder layout follows the PK3 conventions (`sprites/`, `flats/`, `music/`, ...),
+/// file names are mapped to lump names with the usual 8 character rules and embedded
+/// WADs are indexed recursively. Files are read in path order, so later files override
+/// earlier ones with the same lump name in a predictable way.
+#[derive(Debug, Clone)]
+pub struct FolderArchive {
+    name: String,
+    files: Vec<ResourceFile>,
+}
+
+impl FolderArchive {
+    /// Reads every file below `root` into memory.
+    ///
+    /// # Arguments
+    /// - `root`: The folder which acts as the root of the virtual WAD.
+    /// # Returns
+    /// - `Ok(FolderArchive)` with every file that maps to a lump name.
+    /// - `Err` if the folder or one of its files can't be read.
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref();
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut paths = Vec::new();
+        Self::collect_files(root, "", &mut paths)?;
+        paths.sort();
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let data = fs::read(root.join(&path))?;
+            // files like ".gitkeep" have no lump name and can't be addressed
+            if let Ok(file) = ResourceFile::new(path, data) {
+                files.push(file);
+            }
+        }
+
+        Ok(Self { name, files })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// All files of the folder in path order
+    pub fn files(&self) -> &[ResourceFile] {
+        &self.files
+    }
+
+    /// Builds the lump tree of the folder, embedded WADs are indexed recursively.
+    pub fn index(&self) -> Result<ResourceIndex<'_>> {
+        ResourceIndex::from_files(self.name.clone(), &self.files)
+    }
+
+    /// Collects the paths of all files below `folder` relative to the root, separated by '/'
+    fn collect_files(folder: &Path, prefix: &str, paths: &mut Vec<String>) -> Result<()> {
+        for entry in fs::read_dir(folder)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let path = format!("{}{}", prefix, file_name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                Self::collect_files(&entry.path(), &format!("{}/", path), paths)?;
+            } else if file_type.is_file() {
+                paths.push(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{LumpNode, LumpSource};
+
+    #[test]
+    fn folder_archive_fails_on_missing_folder() {
+        let result = FolderArchive::open("this/folder/does/not/exist");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn folder_archive_indexes_loose_files() {
+        let root = std::env::temp_dir().join(format!("wad-rs-folder-{}", std::process::id()));
+        fs::create_dir_all(root.join("sprites/monsters")).unwrap();
+        fs::create_dir_all(root.join("music")).unwrap();
+        fs::write(root.join("sprites/monsters/trooa1.lmp"), [1, 2, 3]).unwrap();
+        fs::write(root.join("music/d_runnin.mid"), [4, 5, 6]).unwrap();
+        fs::write(root.join("playpal.lmp"), [7, 8, 9]).unwrap();
+        fs::write(root.join(".gitkeep"), []).unwrap();
+
+        let archive = FolderArchive::open(&root);
+        fs::remove_dir_all(&root).unwrap();
+        let archive = archive.unwrap();
+        let index = archive.index().unwrap();
+
+        assert_eq!(archive.files().len(), 3);
+        match index.get_lump(vec!["S_START"], "TROOA1").unwrap() {
+            LumpNode::Lump { lump, .. } => assert_eq!(lump.data(), &[1, 2, 3]),
+            _ => panic!("TROOA1 is not a lump"),
+        }
+        assert!(index.get_lump(vec![], "D_RUNNIN").is_some());
+        assert!(index.get_lump(vec![], "PLAYPAL").is_some());
+    }
+}
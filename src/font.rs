@@ -0,0 +1,509 @@
+use crate::graphics::{IndexedImage, Palette};
+use crate::index::{LumpNode, LumpSource};
+use crate::sprite::Sprite;
+use crate::translation::Translation;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Number of glyphs a font can hold, fonts are indexed by byte
+const GLYPH_COUNT: usize = 256;
+
+/// Width of the space character if the font has no glyph to derive it from
+const DEFAULT_SPACE_WIDTH: usize = 4;
+
+/// A single character of a font
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    /// The palette indices of the glyph, see `WadFont` for FON2 fonts
+    pub image: IndexedImage,
+    pub left_offset: i32,
+    pub top_offset: i32,
+}
+
+impl Glyph {
+    pub fn width(&self) -> usize {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.image.height()
+    }
+}
+
+/// A bitmap font built from WAD lumps.
+///
+/// Patch fonts are collected from one patch lump per character, like the HUD font of Doom
+/// (STCFN033 to STCFN121) or the FONTA and FONTB fonts of Heretic and Hexen. ZDoom fonts
+/// are single FON1 or FON2 lumps.
+///
+/// Glyph pixels are palette indices, text is rendered by passing them through an optional
+/// translation and the palette. FON1 fonts store shades instead, which are meant to be
+/// colored with a translation. FON2 fonts bring their own palette, their glyphs are mapped
+/// to the nearest colors of the palette used for rendering before the translation applies.
+#[derive(Debug, Clone)]
+pub struct WadFont {
+    glyphs: Vec<Option<Glyph>>,
+    height: usize,
+    space_width: usize,
+    kerning: i32,
+    palette: Option<Palette<'static>>,
+}
+
+impl WadFont {
+    /// Collects a font from one patch lump per character.
+    ///
+    /// # Arguments
+    /// - `source`: The lump tree, the patches are looked up at the top level.
+    /// - `lump_name`: Returns the lump name of a character.
+    /// # Returns
+    /// - `Ok(WadFont)` with every character that has a lump.
+    /// - `Err` if no character has a lump or a lump is no valid patch.
+    pub fn from_patch_lumps<'a, S, F>(source: &S, lump_name: F) -> Result<Self>
+    where
+        S: LumpSource<'a>,
+        F: Fn(u8) -> String,
+    {
+        let mut glyphs = vec![None; GLYPH_COUNT];
+        for (character, glyph) in glyphs.iter_mut().enumerate() {
+            let Some(LumpNode::Lump { lump, .. }) =
+                source.get_lump(vec![], &lump_name(character as u8))
+            else {
+                continue;
+            };
+
+            let data = lump.decompressed()?;
+            let patch = Sprite::new(&data)?;
+            *glyph = Some(Glyph {
+                image: patch.indexed_image()?,
+                left_offset: patch.left_offset() as i32,
+                top_offset: patch.top_offset() as i32,
+            });
+        }
+
+        let height = glyphs
+            .iter()
+            .flatten()
+            .map(Glyph::height)
+            .max()
+            .ok_or("font has no glyphs")?;
+        // like ZDoom, the space is half as wide as the letter N
+        let space_width = glyphs[b'N' as usize]
+            .as_ref()
+            .map_or(DEFAULT_SPACE_WIDTH, |glyph| glyph.width().div_ceil(2));
+
+        Ok(Self {
+            glyphs,
+            height,
+            space_width,
+            kerning: 0,
+            palette: None,
+        })
+    }
+
+    /// Collects the Doom HUD font from the STCFNxxx lumps, named by decimal character code
+    pub fn doom_hud<'a, S: LumpSource<'a>>(source: &S) -> Result<Self> {
+        Self::from_patch_lumps(source, |character| format!("STCFN{character:03}"))
+    }
+
+    /// Collects a Heretic or Hexen font like FONTA or FONTB.
+    ///
+    /// The lumps are numbered from 1 for the character '!', e.g. FONTA01 to FONTA59.
+    pub fn heretic<'a, S: LumpSource<'a>>(source: &S, prefix: &str) -> Result<Self> {
+        Self::from_patch_lumps(source, |character| match character {
+            33..=131 => format!("{prefix}{:02}", character - 32),
+            _ => String::new(),
+        })
+    }
+
+    /// Parses a ZDoom FON1 or FON2 font lump.
+    ///
+    /// # Structure of a FON1 Lump
+    /// - 4 bytes: "FON1"
+    /// - 2 bytes: Character width (u16, little-endian)
+    /// - 2 bytes: Character height (u16, little-endian)
+    /// - 256 glyphs of width * height shades, each compressed with ByteRun1
+    ///
+    /// # Structure of a FON2 Lump
+    /// - 4 bytes: "FON2"
+    /// - 2 bytes: Font height (u16, little-endian)
+    /// - 1 byte each: First character, last character, constant width flag,
+    ///   shading type, palette size - 1 and flags (bit 0: kerning follows)
+    /// - 2 bytes: Kerning (i16, little-endian), only if flag bit 0 is set
+    /// - 2 bytes per character: Widths (u16, little-endian), a single width if the
+    ///   constant width flag is set
+    /// - 3 bytes per palette color, color 0 is transparent
+    /// - The glyphs with a width above 0, each compressed with ByteRun1
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        match data.get(..4) {
+            Some(b"FON1") => Self::parse_fon1(data),
+            Some(b"FON2") => Self::parse_fon2(data),
+            _ => Err("unknown font format".into()),
+        }
+    }
+
+    fn parse_fon1(data: &[u8]) -> Result<Self> {
+        let header = data.get(4..8).ok_or("FON1 header too short")?;
+        let width = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let height = u16::from_le_bytes([header[2], header[3]]) as usize;
+
+        let mut cursor = 8;
+        let mut glyphs = Vec::with_capacity(GLYPH_COUNT);
+        for _ in 0..GLYPH_COUNT {
+            let pixels = unpack_byte_run(data, &mut cursor, width * height)?;
+            glyphs.push(Some(Glyph {
+                image: image_from_pixels(width, height, &pixels),
+                left_offset: 0,
+                top_offset: 0,
+            }));
+        }
+
+        Ok(Self {
+            glyphs,
+            height,
+            space_width: width,
+            kerning: 0,
+            palette: None,
+        })
+    }
+
+    fn parse_fon2(data: &[u8]) -> Result<Self> {
+        let header = data.get(4..12).ok_or("FON2 header too short")?;
+        let height = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let (first, last) = (header[2] as usize, header[3] as usize);
+        let constant_width = header[4] != 0;
+        let palette_size = header[6] as usize + 1;
+        let has_kerning = header[7] & 1 != 0;
+        if last < first {
+            return Err("FON2 character range is empty".into());
+        }
+
+        let mut cursor = 12;
+        let read_u16 = |cursor: &mut usize| -> Result<u16> {
+            let bytes = data
+                .get(*cursor..*cursor + 2)
+                .ok_or("FON2 data too short")?;
+            *cursor += 2;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+        };
+
+        let kerning = if has_kerning {
+            read_u16(&mut cursor)? as i16 as i32
+        } else {
+            0
+        };
+
+        let count = last - first + 1;
+        let widths = if constant_width {
+            vec![read_u16(&mut cursor)? as usize; count]
+        } else {
+            (0..count)
+                .map(|_| read_u16(&mut cursor).map(usize::from))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let palette_data = data
+            .get(cursor..cursor + palette_size * 3)
+            .ok_or("FON2 palette too short")?;
+        let mut colors = [[0u8; 3]; GLYPH_COUNT];
+        for (color, rgb) in colors.iter_mut().zip(palette_data.chunks_exact(3)) {
+            color.copy_from_slice(rgb);
+        }
+        cursor += palette_size * 3;
+
+        let mut glyphs = vec![None; GLYPH_COUNT];
+        for (character, &width) in (first..=last).zip(&widths) {
+            if width == 0 {
+                continue;
+            }
+            let pixels = unpack_byte_run(data, &mut cursor, width * height)?;
+            glyphs[character] = Some(Glyph {
+                image: image_from_pixels(width, height, &pixels),
+                left_offset: 0,
+                top_offset: 0,
+            });
+        }
+
+        // ZDoom uses the width of the space glyph, or the width of N if there is none
+        let space_width = [b' ', b'N']
+            .iter()
+            .filter_map(|&character| character.checked_sub(first as u8))
+            .find_map(|index| widths.get(index as usize).filter(|&&width| width > 0))
+            .copied()
+            .unwrap_or(DEFAULT_SPACE_WIDTH);
+
+        Ok(Self {
+            glyphs,
+            height,
+            space_width,
+            kerning,
+            palette: Some(Palette::from_colors(colors)),
+        })
+    }
+
+    /// Returns the glyph of a character. Patch fonts often only have upper case letters,
+    /// lower case letters fall back to them like in the original games.
+    pub fn glyph(&self, character: char) -> Option<&Glyph> {
+        let lookup = |character: char| {
+            u8::try_from(character)
+                .ok()
+                .and_then(|code| self.glyphs[code as usize].as_ref())
+        };
+        lookup(character).or_else(|| lookup(character.to_ascii_uppercase()))
+    }
+
+    /// Height of the font, the height of the tallest glyph for patch fonts
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Advance of characters without glyph, like the space
+    pub fn space_width(&self) -> usize {
+        self.space_width
+    }
+
+    /// Extra space between characters, only FON2 fonts have kerning
+    pub fn kerning(&self) -> i32 {
+        self.kerning
+    }
+
+    /// The own palette of FON2 fonts
+    pub fn palette(&self) -> Option<&Palette<'static>> {
+        self.palette.as_ref()
+    }
+
+    /// Horizontal distance from the start of a character to the start of the next one
+    pub fn advance(&self, character: char) -> i32 {
+        let width = self.glyph(character).map_or(self.space_width, Glyph::width);
+        width as i32 + self.kerning
+    }
+
+    /// Width of a single line of text in pixels
+    pub fn text_width(&self, text: &str) -> usize {
+        let width: i32 = text.chars().map(|character| self.advance(character)).sum();
+        // no kerning after the last character
+        let trailing = if text.is_empty() { 0 } else { self.kerning };
+        (width - trailing).max(0) as usize
+    }
+
+    /// Renders a single line of text into an RGBA pixel buffer.
+    ///
+    /// The buffer is `text_width(text)` pixels wide and `height()` pixels high, glyphs are
+    /// placed like patches with their offsets. Pixels without glyph stay transparent.
+    ///
+    /// # Arguments
+    /// - `text`: The text, characters without glyph advance by the space width.
+    /// - `palette`: The palette to draw the glyphs with.
+    /// - `translation`: Remaps the glyph colors, e.g. for colored menu text.
+    /// # Returns
+    /// - `Ok(Vec<u8>)` with the rendered text.
+    /// - `Err` if a palette index is out of bounds.
+    pub fn rgba_pixel_buffer(
+        &self,
+        text: &str,
+        palette: &Palette,
+        translation: Option<&Translation>,
+    ) -> Result<Vec<u8>> {
+        let width = self.text_width(text);
+        let mut pixel_buffer = vec![0u8; width * self.height * 4];
+
+        // FON2 colors are mapped into the target palette once
+        let mut colors = Translation::identity().table().to_owned();
+        if let Some(own_palette) = &self.palette {
+            for (index, color) in colors.iter_mut().enumerate() {
+                let rgb = own_palette
+                    .get_rgb(index)
+                    .ok_or("palette index out of bounds")?;
+                *color = palette.nearest_index(*rgb);
+            }
+        }
+        if let Some(translation) = translation {
+            translation.translate_indices(&mut colors);
+        }
+
+        let mut pen_x = 0i32;
+        for character in text.chars() {
+            if let Some(glyph) = self.glyph(character) {
+                let origin_x = pen_x - glyph.left_offset;
+                let origin_y = -glyph.top_offset;
+                for y in 0..glyph.height() {
+                    for x in 0..glyph.width() {
+                        let Some(index) = glyph.image.get(x, y) else {
+                            continue;
+                        };
+                        let (px, py) = (origin_x + x as i32, origin_y + y as i32);
+                        if px < 0 || py < 0 || px as usize >= width || py as usize >= self.height {
+                            continue;
+                        }
+
+                        let pos = (py as usize * width + px as usize) * 4;
+                        pixel_buffer[pos..pos + 4].copy_from_slice(
+                            &palette
+                                .get_rgba(colors[index as usize] as usize)
+                                .ok_or("palette index out of bounds")?,
+                        );
+                    }
+                }
+            }
+            pen_x += self.advance(character);
+        }
+
+        Ok(pixel_buffer)
+    }
+}
+
+impl TryFrom<&[u8]> for WadFont {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        WadFont::from_bytes(value)
+    }
+}
+
+/// Decompresses ByteRun1 (PackBits) data: a code below 0x80 copies the next `code + 1`
+/// bytes, a code above 0x80 repeats the next byte `257 - code` times, 0x80 is skipped.
+fn unpack_byte_run(data: &[u8], cursor: &mut usize, size: usize) -> Result<Vec<u8>> {
+    // the size comes from the header, the remaining data unpacks to at most 128 bytes
+    // per 2 bytes
+    let unpacked_limit = (data.len().saturating_sub(*cursor) / 2).saturating_mul(128);
+    let mut output = Vec::with_capacity(size.min(unpacked_limit));
+    while output.len() < size {
+        let code = *data.get(*cursor).ok_or("font glyph data too short")?;
+        *cursor += 1;
+        match code {
+            0x00..=0x7F => {
+                let length = code as usize + 1;
+                let run = data
+                    .get(*cursor..*cursor + length)
+                    .ok_or("font glyph data too short")?;
+                output.extend_from_slice(run);
+                *cursor += length;
+            }
+            0x80 => {}
+            _ => {
+                let value = *data.get(*cursor).ok_or("font glyph data too short")?;
+                *cursor += 1;
+                output.extend(std::iter::repeat_n(value, 257 - code as usize));
+            }
+        }
+    }
+
+    if output.len() > size {
+        return Err("font glyph run exceeds glyph size".into());
+    }
+
+    Ok(output)
+}
+
+/// Builds a glyph image, index 0 is transparent
+fn image_from_pixels(width: usize, height: usize, pixels: &[u8]) -> IndexedImage {
+    let mut image = IndexedImage::new(width, height);
+    for (i, &index) in pixels.iter().enumerate() {
+        if index != 0 {
+            image.set(i % width, i / width, index);
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::wad::{WadIndex, WadWriter};
+
+    /// Palette where color n is (n, n, n)
+    fn gray_palette_data() -> Vec<u8> {
+        (0..768).map(|val: u16| (val / 3) as u8).collect()
+    }
+
+    /// Patch of `width` x 1 pixels in one color
+    fn patch(width: u8, index: u8) -> Vec<u8> {
+        let mut data = vec![width, 0, 1, 0, 0, 0, 0, 0];
+        let columns_start = 8 + width as u32 * 4;
+        for column in 0..width as u32 {
+            data.extend(&(columns_start + column * 6).to_le_bytes());
+        }
+        for _ in 0..width {
+            data.extend(&[0, 1, 0, index, 0, 0xFF]);
+        }
+        data
+    }
+
+    #[test]
+    fn patch_fonts_collect_glyphs() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_lump("STCFN065", patch(2, 10)).unwrap();
+        wad.add_lump("STCFN078", patch(5, 20)).unwrap();
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+
+        let font = WadFont::doom_hud(&index).unwrap();
+        assert_eq!(font.height(), 1);
+        assert_eq!(font.space_width(), 3);
+        assert_eq!(font.glyph('a').unwrap().width(), 2);
+        assert!(font.glyph('B').is_none());
+        assert_eq!(font.text_width("A N"), 2 + 3 + 5);
+
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let translation = Translation::from_bytes(&[7u8; 256]).unwrap();
+        let rgba = font
+            .rgba_pixel_buffer("A N", &palette, Some(&translation))
+            .unwrap();
+        assert_eq!(rgba.len(), 10 * 4);
+        assert_eq!(&rgba[0..4], &[7, 7, 7, 255]);
+        assert_eq!(&rgba[8..12], &[0, 0, 0, 0]);
+
+        assert!(WadFont::heretic(&index, "FONTA").is_err());
+    }
+
+    #[test]
+    fn fon1_fonts_can_be_parsed() {
+        let mut data = b"FON1".to_vec();
+        data.extend(&[2, 0, 2, 0]);
+        // every glyph is a run of 4 pixels with shade 3
+        for _ in 0..GLYPH_COUNT {
+            data.extend(&[0xFD, 3]);
+        }
+        // glyph 'A' is stored literally
+        let a = 8 + b'A' as usize * 2;
+        data.splice(a..a + 2, [3, 0, 1, 2, 3]);
+
+        let font = WadFont::from_bytes(&data).unwrap();
+        assert_eq!((font.height(), font.space_width()), (2, 2));
+        let glyph = font.glyph('A').unwrap();
+        assert_eq!(glyph.image.get(0, 0), None);
+        assert_eq!(glyph.image.get(1, 1), Some(3));
+        assert_eq!(font.glyph('B').unwrap().image.get(0, 0), Some(3));
+        assert!(WadFont::from_bytes(&data[..100]).is_err());
+
+        // a huge glyph size in the header is not allocated up front
+        let mut huge = b"FON1".to_vec();
+        huge.extend(&[0xFF, 0xFF, 0xFF, 0xFF, 0x81, 0]);
+        assert!(WadFont::from_bytes(&huge).is_err());
+    }
+
+    #[test]
+    fn fon2_fonts_map_their_palette() {
+        let mut data = b"FON2".to_vec();
+        // height 1, characters 'A'-'B', variable widths, 2 colors, kerning
+        data.extend(&[1, 0, b'A', b'B', 0, 0, 1, 1]);
+        data.extend(&(-1i16).to_le_bytes());
+        data.extend(&[2, 0, 0, 0]);
+        data.extend(&[0, 0, 0, 50, 50, 50]);
+        data.extend(&[1, 1, 0]);
+
+        let font = WadFont::from_bytes(&data).unwrap();
+        assert_eq!(font.kerning(), -1);
+        assert!(font.glyph('B').is_none());
+        assert_eq!(font.space_width(), DEFAULT_SPACE_WIDTH);
+        assert_eq!(font.text_width("AA"), 3);
+
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let rgba = font.rgba_pixel_buffer("A", &palette, None).unwrap();
+        assert_eq!(rgba, vec![50, 50, 50, 255, 0, 0, 0, 0]);
+    }
+}
@@ -1,14 +1,119 @@
+use crate::rgba::RgbaTable;
+use std::borrow::Cow;
+
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
+/// Size of a single palette in bytes (256 RGB colors)
+pub const PALETTE_SIZE: usize = 768;
+
+/// Number of gamma correction levels of vanilla Doom (`usegamma` 0-4)
+pub const GAMMA_LEVELS: usize = 5;
+
+/// Returns the gamma correction table of a vanilla gamma level.
+///
+/// Level 0 is almost the identity, every level brightens the dark colors more. These are
+/// the hard coded tables of vanilla Doom (`gammatable` of v_video.c), entry `i` follows
+/// the curve `255 * ((i + 1) / 256) ^ (1 - level / 8)`, so even level 0 maps 0 to 1.
+///
+/// # Returns
+/// - `Some([u8; 256])` mapping each color component to its corrected value.
+/// - `None` if the level is not below `GAMMA_LEVELS`.
+pub fn gamma_table(level: usize) -> Option<[u8; 256]> {
+    GAMMA_TABLE.get(level).copied()
+}
+
+/// `gammatable` of v_video.c
+const GAMMA_TABLE: [[u8; 256]; GAMMA_LEVELS] = [
+    [
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+        26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48,
+        49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71,
+        72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94,
+        95, 96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113,
+        114, 115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127, 128, 128, 129, 130,
+        131, 132, 133, 134, 135, 136, 137, 138, 139, 140, 141, 142, 143, 144, 145, 146, 147, 148,
+        149, 150, 151, 152, 153, 154, 155, 156, 157, 158, 159, 160, 161, 162, 163, 164, 165, 166,
+        167, 168, 169, 170, 171, 172, 173, 174, 175, 176, 177, 178, 179, 180, 181, 182, 183, 184,
+        185, 186, 187, 188, 189, 190, 191, 192, 193, 194, 195, 196, 197, 198, 199, 200, 201, 202,
+        203, 204, 205, 206, 207, 208, 209, 210, 211, 212, 213, 214, 215, 216, 217, 218, 219, 220,
+        221, 222, 223, 224, 225, 226, 227, 228, 229, 230, 231, 232, 233, 234, 235, 236, 237, 238,
+        239, 240, 241, 242, 243, 244, 245, 246, 247, 248, 249, 250, 251, 252, 253, 254, 255,
+    ],
+    [
+        2, 4, 5, 7, 8, 10, 11, 12, 14, 15, 16, 18, 19, 20, 21, 23, 24, 25, 26, 27, 29, 30, 31, 32,
+        33, 34, 36, 37, 38, 39, 40, 41, 42, 44, 45, 46, 47, 48, 49, 50, 51, 52, 54, 55, 56, 57, 58,
+        59, 60, 61, 62, 63, 64, 65, 66, 67, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82,
+        83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 100, 101, 102, 103,
+        104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121,
+        122, 123, 124, 125, 126, 127, 128, 129, 129, 130, 131, 132, 133, 134, 135, 136, 137, 138,
+        139, 140, 141, 142, 143, 144, 145, 146, 147, 148, 148, 149, 150, 151, 152, 153, 154, 155,
+        156, 157, 158, 159, 160, 161, 162, 163, 163, 164, 165, 166, 167, 168, 169, 170, 171, 172,
+        173, 174, 175, 175, 176, 177, 178, 179, 180, 181, 182, 183, 184, 185, 186, 186, 187, 188,
+        189, 190, 191, 192, 193, 194, 195, 196, 196, 197, 198, 199, 200, 201, 202, 203, 204, 205,
+        205, 206, 207, 208, 209, 210, 211, 212, 213, 214, 214, 215, 216, 217, 218, 219, 220, 221,
+        222, 222, 223, 224, 225, 226, 227, 228, 229, 230, 230, 231, 232, 233, 234, 235, 236, 237,
+        237, 238, 239, 240, 241, 242, 243, 244, 245, 245, 246, 247, 248, 249, 250, 251, 252, 252,
+        253, 254, 255,
+    ],
+    [
+        4, 7, 9, 11, 13, 15, 17, 19, 21, 22, 24, 26, 27, 29, 30, 32, 33, 35, 36, 38, 39, 40, 42,
+        43, 45, 46, 47, 48, 50, 51, 52, 54, 55, 56, 57, 59, 60, 61, 62, 63, 65, 66, 67, 68, 69, 70,
+        72, 73, 74, 75, 76, 77, 78, 79, 80, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95,
+        96, 97, 98, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 114,
+        115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127, 128, 129, 130, 131, 132,
+        133, 133, 134, 135, 136, 137, 138, 139, 140, 141, 142, 143, 144, 144, 145, 146, 147, 148,
+        149, 150, 151, 152, 153, 153, 154, 155, 156, 157, 158, 159, 160, 160, 161, 162, 163, 164,
+        165, 166, 166, 167, 168, 169, 170, 171, 172, 172, 173, 174, 175, 176, 177, 178, 178, 179,
+        180, 181, 182, 183, 183, 184, 185, 186, 187, 188, 188, 189, 190, 191, 192, 193, 193, 194,
+        195, 196, 197, 197, 198, 199, 200, 201, 201, 202, 203, 204, 205, 206, 206, 207, 208, 209,
+        210, 210, 211, 212, 213, 213, 214, 215, 216, 217, 217, 218, 219, 220, 221, 221, 222, 223,
+        224, 224, 225, 226, 227, 228, 228, 229, 230, 231, 231, 232, 233, 234, 235, 235, 236, 237,
+        238, 238, 239, 240, 241, 241, 242, 243, 244, 244, 245, 246, 247, 247, 248, 249, 250, 251,
+        251, 252, 253, 254, 254, 255,
+    ],
+    [
+        8, 12, 16, 19, 22, 24, 27, 29, 31, 34, 36, 38, 40, 41, 43, 45, 47, 49, 50, 52, 53, 55, 57,
+        58, 60, 61, 63, 64, 65, 67, 68, 70, 71, 72, 74, 75, 76, 77, 79, 80, 81, 82, 84, 85, 86, 87,
+        88, 90, 91, 92, 93, 94, 95, 96, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109,
+        110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127,
+        128, 129, 130, 131, 132, 133, 134, 135, 135, 136, 137, 138, 139, 140, 141, 142, 143, 143,
+        144, 145, 146, 147, 148, 149, 150, 150, 151, 152, 153, 154, 155, 155, 156, 157, 158, 159,
+        160, 160, 161, 162, 163, 164, 165, 165, 166, 167, 168, 169, 169, 170, 171, 172, 173, 173,
+        174, 175, 176, 176, 177, 178, 179, 180, 180, 181, 182, 183, 183, 184, 185, 186, 186, 187,
+        188, 189, 189, 190, 191, 192, 192, 193, 194, 195, 195, 196, 197, 197, 198, 199, 200, 200,
+        201, 202, 202, 203, 204, 205, 205, 206, 207, 207, 208, 209, 210, 210, 211, 212, 212, 213,
+        214, 214, 215, 216, 216, 217, 218, 219, 219, 220, 221, 221, 222, 223, 223, 224, 225, 225,
+        226, 227, 227, 228, 229, 229, 230, 231, 231, 232, 233, 233, 234, 235, 235, 236, 237, 237,
+        238, 238, 239, 240, 240, 241, 242, 242, 243, 244, 244, 245, 246, 246, 247, 247, 248, 249,
+        249, 250, 251, 251, 252, 253, 253, 254, 254, 255,
+    ],
+    [
+        16, 23, 28, 32, 36, 39, 42, 45, 48, 50, 53, 55, 57, 60, 62, 64, 66, 68, 69, 71, 73, 75, 76,
+        78, 80, 81, 83, 84, 86, 87, 89, 90, 92, 93, 94, 96, 97, 98, 100, 101, 102, 103, 105, 106,
+        107, 108, 109, 110, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125,
+        126, 128, 128, 129, 130, 131, 132, 133, 134, 135, 136, 137, 138, 139, 140, 141, 142, 143,
+        143, 144, 145, 146, 147, 148, 149, 150, 150, 151, 152, 153, 154, 155, 155, 156, 157, 158,
+        159, 159, 160, 161, 162, 163, 163, 164, 165, 166, 166, 167, 168, 169, 169, 170, 171, 172,
+        172, 173, 174, 175, 175, 176, 177, 177, 178, 179, 180, 180, 181, 182, 182, 183, 184, 184,
+        185, 186, 187, 187, 188, 189, 189, 190, 191, 191, 192, 193, 193, 194, 195, 195, 196, 196,
+        197, 198, 198, 199, 200, 200, 201, 202, 202, 203, 203, 204, 205, 205, 206, 207, 207, 208,
+        208, 209, 210, 210, 211, 211, 212, 213, 213, 214, 214, 215, 216, 216, 217, 217, 218, 219,
+        219, 220, 220, 221, 221, 222, 223, 223, 224, 224, 225, 225, 226, 227, 227, 228, 228, 229,
+        229, 230, 230, 231, 232, 232, 233, 233, 234, 234, 235, 235, 236, 236, 237, 237, 238, 239,
+        239, 240, 240, 241, 241, 242, 242, 243, 243, 244, 244, 245, 245, 246, 246, 247, 247, 248,
+        248, 249, 249, 250, 250, 251, 251, 252, 252, 253, 254, 254, 255, 255,
+    ],
+];
+
 #[derive(Debug, Clone)]
 pub struct Palette<'a> {
-    colors: &'a [[u8; 3]; 256],
+    colors: Cow<'a, [[u8; 3]; 256]>,
 }
 
-impl Palette<'_> {
-    pub fn from_bytes(data: &[u8]) -> Result<Self> {
-        if data.len() < 768 {
+impl<'a> Palette<'a> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        if data.len() < PALETTE_SIZE {
             return Err("Palette data too short".into());
         }
 
@@ -16,7 +121,16 @@ impl Palette<'_> {
         // which is enough to hold 256 RGB entries (3 bytes each).
         let colors = unsafe { &*(data.as_ptr() as *const [[u8; 3]; 256]) };
 
-        Ok(Self { colors })
+        Ok(Self {
+            colors: Cow::Borrowed(colors),
+        })
+    }
+
+    /// Creates a palette which owns its colors, e.g. a palette shaded by a colormap
+    pub fn from_colors(colors: [[u8; 3]; 256]) -> Palette<'static> {
+        Palette {
+            colors: Cow::Owned(colors),
+        }
     }
 
     pub fn get_rgb(&self, index: usize) -> Option<&[u8; 3]> {
@@ -27,16 +141,248 @@ impl Palette<'_> {
         self.colors.get(index).map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
     }
 
+    /// Returns the index of the color closest to `rgb` (squared RGB distance),
+    /// the lowest index wins if several colors are equally close.
+    pub fn nearest_index(&self, rgb: [u8; 3]) -> u8 {
+        let distance = |color: &[u8; 3]| {
+            color
+                .iter()
+                .zip(rgb)
+                .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+
+        self.colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, color)| distance(color))
+            .map(|(index, _)| index as u8)
+            .unwrap_or(0)
+    }
+
+    /// Builds the palette as the player sees it at a gamma level, see `gamma_table`
+    pub fn with_gamma(&self, level: usize) -> Result<Palette<'static>> {
+        let table = gamma_table(level).ok_or("gamma level out of range")?;
+        let mut colors = *self.colors;
+        for component in colors.iter_mut().flatten() {
+            *component = table[*component as usize];
+        }
+
+        Ok(Palette::from_colors(colors))
+    }
 }
 
-impl TryFrom<&[u8]> for Palette<'_> {
+impl<'a> TryFrom<&'a [u8]> for Palette<'a> {
     type Error = Error;
 
-    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
         Palette::from_bytes(value)
     }
 }
 
+/// The purpose of a palette in PLAYPAL, used to pick the palette for screen flashes.
+///
+/// Strengths start at 1, a higher strength is a more intense tint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaletteRole {
+    /// The palette everything is drawn with
+    Normal,
+    /// Red tint when taking damage or using berserk, strength 1-8
+    Damage(u8),
+    /// Yellow tint when picking up items, strength 1-4
+    Bonus(u8),
+    /// Green tint while wearing the radiation suit (Doom only)
+    RadiationSuit,
+    /// Green tint when poisoned (Hexen only), strength 1-8
+    Poison(u8),
+    /// Blue tint when frozen (Hexen only)
+    Ice,
+    /// White flash of the Wraithverge (Hexen only), strength 1-3
+    Holy(u8),
+    /// Flash of the Bloodscourge (Hexen only), strength 1-3
+    Scourge(u8),
+}
+
+/// Number of palettes in the PLAYPAL of Doom and Strife
+const DOOM_PALETTE_COUNT: usize = 14;
+/// Number of palettes in the PLAYPAL of Hexen
+const HEXEN_PALETTE_COUNT: usize = 28;
+
+/// All palettes of a PLAYPAL lump.
+///
+/// # Structure of a PLAYPAL Lump
+/// PLAYPAL is a sequence of 768 byte palettes, the first one is the normal palette.
+/// The number of palettes depends on the game:
+/// - Heretic: 13 (normal, 8 damage, 4 bonus)
+/// - Doom and Strife: 14 (like Heretic plus the radiation suit)
+/// - Hexen: 28 (normal, 8 damage, 4 bonus, 8 poison, ice, 3 Wraithverge, 3 Bloodscourge)
+///
+/// The layout is derived from the palette count, roles which don't exist in the lump
+/// return `None`.
+#[derive(Debug, Clone)]
+pub struct PaletteSet<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PaletteSet<'a> {
+    /// Creates a `PaletteSet` from the PLAYPAL lump data.
+    ///
+    /// # Arguments
+    /// - `data`: The PLAYPAL lump data, trailing bytes after the last full palette are ignored.
+    /// # Returns
+    /// - `Ok(PaletteSet)` if the lump holds at least one palette.
+    /// - `Err` if the lump is shorter than a single palette.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        if data.len() < PALETTE_SIZE {
+            return Err("PLAYPAL data too short".into());
+        }
+
+        Ok(Self { data })
+    }
+
+    /// Number of palettes in the lump
+    pub fn count(&self) -> usize {
+        self.data.len() / PALETTE_SIZE
+    }
+
+    pub fn get(&self, index: usize) -> Option<Palette<'a>> {
+        if index >= self.count() {
+            return None;
+        }
+
+        let start = index * PALETTE_SIZE;
+        Palette::from_bytes(&self.data[start..start + PALETTE_SIZE]).ok()
+    }
+
+    pub fn get_by_role(&self, role: PaletteRole) -> Option<Palette<'a>> {
+        self.get(self.role_index(role)?)
+    }
+
+    /// Returns the normal palette, which always exists
+    pub fn normal(&self) -> Palette<'a> {
+        self.get(0).expect("PaletteSet holds at least one palette")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Palette<'a>> + '_ {
+        (0..self.count()).filter_map(|index| self.get(index))
+    }
+
+    /// Applies a gamma level to every palette, see `gamma_table`.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)` with the corrected palettes in the PLAYPAL layout, which can be
+    ///   loaded with `PaletteSet::from_bytes` to keep the palette roles.
+    /// - `Err` if the gamma level is out of range.
+    pub fn gamma_corrected_data(&self, level: usize) -> Result<Vec<u8>> {
+        let table = gamma_table(level).ok_or("gamma level out of range")?;
+        Ok(self.data[..self.count() * PALETTE_SIZE]
+            .iter()
+            .map(|&component| table[component as usize])
+            .collect())
+    }
+
+    fn role_index(&self, role: PaletteRole) -> Option<usize> {
+        let hexen = self.count() == HEXEN_PALETTE_COUNT;
+        let strength = |strength: u8, max: u8, first: usize| {
+            (1..=max)
+                .contains(&strength)
+                .then(|| first + strength as usize - 1)
+        };
+
+        match role {
+            PaletteRole::Normal => Some(0),
+            PaletteRole::Damage(n) => strength(n, 8, 1),
+            PaletteRole::Bonus(n) => strength(n, 4, 9),
+            PaletteRole::RadiationSuit if self.count() == DOOM_PALETTE_COUNT => Some(13),
+            PaletteRole::Poison(n) if hexen => strength(n, 8, 13),
+            PaletteRole::Ice if hexen => Some(21),
+            PaletteRole::Holy(n) if hexen => strength(n, 3, 22),
+            PaletteRole::Scourge(n) if hexen => strength(n, 3, 25),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PaletteSet<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        PaletteSet::from_bytes(value)
+    }
+}
+
+/// A palette indexed image with a transparency mask.
+///
+/// Used for pictures which are assembled from other pictures (e.g. composed wall textures),
+/// so they can be shaded with a colormap before they are converted to RGBA.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    mask: Vec<bool>,
+}
+
+impl IndexedImage {
+    /// Creates a fully transparent image
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            mask: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The palette indices row by row, transparent pixels are 0
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Mutable palette indices row by row, e.g. to remap them through a table
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    /// `true` for every opaque pixel, row by row
+    pub fn mask(&self) -> &[bool] {
+        &self.mask
+    }
+
+    /// Returns the palette index at the position, `None` if the pixel is transparent
+    /// or out of range
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let pos = y * self.width + x;
+        self.mask[pos].then_some(self.pixels[pos])
+    }
+
+    /// Sets an opaque pixel, positions out of range are ignored
+    pub fn set(&mut self, x: usize, y: usize, index: u8) {
+        if x < self.width && y < self.height {
+            let pos = y * self.width + x;
+            self.pixels[pos] = index;
+            self.mask[pos] = true;
+        }
+    }
+
+    /// Converts the image into an RGBA pixel buffer, transparent pixels stay (0, 0, 0, 0)
+    pub fn rgba_pixel_buffer(&self, palette: &Palette) -> Result<Vec<u8>> {
+        Ok(RgbaTable::new(palette).convert_masked(&self.pixels, &self.mask))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,6 +394,44 @@ mod tests {
         assert_eq!(palette.colors.len(), 256);
     }
 
+    #[test]
+    fn gamma_tables_match_vanilla_values() {
+        let first_entries = [
+            [1, 2, 3, 4],
+            [2, 4, 5, 7],
+            [4, 7, 9, 11],
+            [8, 12, 16, 19],
+            [16, 23, 28, 32],
+        ];
+        let last_entries = [
+            [252, 253, 254, 255],
+            [252, 253, 254, 255],
+            [253, 254, 254, 255],
+            [253, 254, 254, 255],
+            [254, 254, 255, 255],
+        ];
+        for level in 0..GAMMA_LEVELS {
+            let table = gamma_table(level).unwrap();
+            assert_eq!(table[..4], first_entries[level]);
+            assert_eq!(table[252..], last_entries[level]);
+        }
+        assert!(gamma_table(GAMMA_LEVELS).is_none());
+    }
+
+    #[test]
+    fn gamma_can_be_applied_to_palettes() {
+        let data: Vec<u8> = (0..768 * 2).map(|val: u16| (val % 3) as u8).collect();
+        let palette = Palette::from_bytes(&data).unwrap().with_gamma(4).unwrap();
+        assert_eq!(palette.get_rgb(0), Some(&[16, 23, 28]));
+        assert!(Palette::from_bytes(&data).unwrap().with_gamma(5).is_err());
+
+        let set = PaletteSet::from_bytes(&data).unwrap();
+        let corrected = set.gamma_corrected_data(4).unwrap();
+        let corrected = PaletteSet::from_bytes(&corrected).unwrap();
+        assert_eq!(corrected.count(), 2);
+        assert_eq!(corrected.get(1).unwrap().get_rgb(0), Some(&[16, 23, 28]));
+    }
+
     #[test]
     fn palette_creation_fails_with_short_data() {
         let data: Vec<u8> = (0..500).map(|val: u16| (val % 256) as u8).collect();
@@ -70,4 +454,75 @@ mod tests {
         assert_eq!(palette.get_rgba(0), Some([0, 1, 2, 255]));
         assert_eq!(palette.get_rgba(255), Some([253, 254, 255, 255]));
     }
+
+    /// Builds a PLAYPAL with `count` palettes, palette n is filled with the value n
+    fn playpal(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|n| vec![n as u8; PALETTE_SIZE])
+            .collect()
+    }
+
+    #[test]
+    fn palette_set_exposes_every_palette() {
+        let data = playpal(14);
+        let palettes = PaletteSet::from_bytes(&data).unwrap();
+        assert_eq!(palettes.count(), 14);
+        assert_eq!(palettes.iter().count(), 14);
+        assert_eq!(palettes.get(5).unwrap().get_rgb(0), Some(&[5, 5, 5]));
+        assert!(palettes.get(14).is_none());
+        assert!(PaletteSet::from_bytes(&data[..700]).is_err());
+    }
+
+    #[test]
+    fn palette_set_maps_doom_roles() {
+        let data = playpal(14);
+        let palettes = PaletteSet::from_bytes(&data).unwrap();
+        let first = |role| palettes.get_by_role(role).map(|p| p.get_rgb(0).unwrap()[0]);
+        assert_eq!(first(PaletteRole::Normal), Some(0));
+        assert_eq!(first(PaletteRole::Damage(1)), Some(1));
+        assert_eq!(first(PaletteRole::Damage(8)), Some(8));
+        assert_eq!(first(PaletteRole::Bonus(4)), Some(12));
+        assert_eq!(first(PaletteRole::RadiationSuit), Some(13));
+        assert_eq!(first(PaletteRole::Damage(9)), None);
+        assert_eq!(first(PaletteRole::Ice), None);
+    }
+
+    #[test]
+    fn palette_set_maps_heretic_and_hexen_roles() {
+        let heretic = playpal(13);
+        let palettes = PaletteSet::from_bytes(&heretic).unwrap();
+        assert!(palettes.get_by_role(PaletteRole::Bonus(4)).is_some());
+        assert!(palettes.get_by_role(PaletteRole::RadiationSuit).is_none());
+
+        let hexen = playpal(28);
+        let palettes = PaletteSet::from_bytes(&hexen).unwrap();
+        let first = |role| palettes.get_by_role(role).map(|p| p.get_rgb(0).unwrap()[0]);
+        assert_eq!(first(PaletteRole::Poison(1)), Some(13));
+        assert_eq!(first(PaletteRole::Ice), Some(21));
+        assert_eq!(first(PaletteRole::Holy(3)), Some(24));
+        assert_eq!(first(PaletteRole::Scourge(3)), Some(27));
+        assert_eq!(first(PaletteRole::RadiationSuit), None);
+    }
+
+    #[test]
+    fn palette_finds_nearest_color() {
+        let data: Vec<u8> = (0..768).map(|val: u16| (val / 3) as u8).collect();
+        let palette = Palette::from_bytes(&data).unwrap();
+        assert_eq!(palette.nearest_index([10, 10, 10]), 10);
+        assert_eq!(palette.nearest_index([10, 11, 13]), 11);
+    }
+
+    #[test]
+    fn indexed_image_tracks_transparency() {
+        let data: Vec<u8> = (0..768).map(|val: u16| (val % 256) as u8).collect();
+        let palette = Palette::from_bytes(&data).unwrap();
+        let mut image = IndexedImage::new(2, 2);
+        image.set(1, 0, 1);
+        image.set(5, 5, 1);
+        assert_eq!(image.get(1, 0), Some(1));
+        assert_eq!(image.get(0, 0), None);
+
+        let rgba = image.rgba_pixel_buffer(&palette).unwrap();
+        assert_eq!(&rgba[0..8], &[0, 0, 0, 0, 3, 4, 5, 255]);
+    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use crate::lump::{LUMP_ENTRY_LENGTH, TEXTURE_LUMP_ENTRY_LENGTH};
 use std::convert::TryFrom;
 
 type Error = Box<dyn std::error::Error>;
@@ -7,6 +8,25 @@ type Result<T> = std::result::Result<T, Error>;
 pub enum MagicString {
     IWAD, // Internal WAD, contains main game data
     PWAD, // Patch WAD; contains custom levels, graphics, etc.
+    WAD2, // Quake texture WAD, 32 byte directory entries
+    WAD3, // Half-Life texture WAD, 32 byte directory entries with embedded palettes
+}
+
+impl MagicString {
+    /// Returns true for the Quake/Half-Life texture WAD formats which use 32 byte
+    /// directory entries with 16 byte lump names.
+    pub fn is_texture_wad(&self) -> bool {
+        matches!(self, MagicString::WAD2 | MagicString::WAD3)
+    }
+
+    /// Size of a single directory entry for this WAD type in bytes.
+    pub fn directory_entry_length(&self) -> usize {
+        if self.is_texture_wad() {
+            TEXTURE_LUMP_ENTRY_LENGTH
+        } else {
+            LUMP_ENTRY_LENGTH
+        }
+    }
 }
 
 impl TryFrom<&[u8; 4]> for MagicString {
@@ -16,6 +36,8 @@ impl TryFrom<&[u8; 4]> for MagicString {
         match bytes {
             b"IWAD" => Ok(MagicString::IWAD),
             b"PWAD" => Ok(MagicString::PWAD),
+            b"WAD2" => Ok(MagicString::WAD2),
+            b"WAD3" => Ok(MagicString::WAD3),
             _ => Err("Invalid WAD header identification".into()),
         }
     }
@@ -43,6 +65,40 @@ impl TryFrom<&[u8; 12]> for Header {
     }
 }
 
+/// Byte order of the header and directory fields.
+/// PC WADs are little-endian, Jaguar and Doom 64 WADs are big-endian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl Header {
+    /// Reads a header with big-endian fields as used by Jaguar and Doom 64 WADs
+    pub fn from_big_endian(bytes: &[u8; 12]) -> Result<Self> {
+        let identification = MagicString::try_from(&[bytes[0], bytes[1], bytes[2], bytes[3]])?;
+        let num_lumps = i32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let info_table_offset = i32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        Ok(Header {
+            identification,
+            num_lumps,
+            info_table_offset,
+        })
+    }
+
+    /// Checks whether the directory described by this header fits into `data_len` bytes
+    pub fn fits(&self, data_len: usize) -> bool {
+        if self.num_lumps < 0 || self.info_table_offset < 0 {
+            return false;
+        }
+
+        (self.num_lumps as usize)
+            .checked_mul(self.identification.directory_entry_length())
+            .and_then(|size| size.checked_add(self.info_table_offset as usize))
+            .is_some_and(|end| end <= data_len)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,6 +115,18 @@ mod tests {
         assert_eq!(pwad_id, MagicString::PWAD);
     }
 
+    #[test]
+    fn header_id_can_convert_texture_wads_from_bytes() {
+        let wad2_id = MagicString::try_from(b"WAD2").unwrap();
+        let wad3_id = MagicString::try_from(b"WAD3").unwrap();
+
+        assert_eq!(wad2_id, MagicString::WAD2);
+        assert_eq!(wad3_id, MagicString::WAD3);
+        assert_eq!(wad2_id.directory_entry_length(), 32);
+        assert_eq!(wad3_id.directory_entry_length(), 32);
+        assert_eq!(MagicString::PWAD.directory_entry_length(), 16);
+    }
+
     #[test]
     fn header_identifies_iwad_from_bytes() {
         let bytes: [u8; 12] = [
@@ -81,6 +149,19 @@ mod tests {
         assert_eq!(header.info_table_offset, 0x5678);
     }
 
+    #[test]
+    fn header_can_read_big_endian_fields() {
+        let bytes: [u8; 12] = [
+            b'I', b'W', b'A', b'D', 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x12, 0x34,
+        ];
+        let header = Header::from_big_endian(&bytes).unwrap();
+        assert_eq!(header.num_lumps, 2);
+        assert_eq!(header.info_table_offset, 0x1234);
+        assert!(header.fits(0x1234 + 32));
+        assert!(!header.fits(0x1234 + 31));
+        assert!(!Header::try_from(&bytes).unwrap().fits(0x1234 + 32));
+    }
+
     #[test]
     fn header_can_detect_invalid_header_id() {
         let bytes: [u8; 12] = [
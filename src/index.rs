@@ -1,79 +1,159 @@
+use crate::audio::SoundSample;
 use crate::lump::LumpRef;
 use crate::tokenizer::{LumpToken, TokenIterator};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::iter::Peekable;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
+/// Lumps of binary maps which belong to the map marker in front of them, in the order the
+/// engine expects them, followed by the extra lumps of Hexen, Strife, ZDoom and Doom 64 maps.
+/// GL nodes (`GL_MAP01`, `GL_VERT`, ...) are recognized by their `GL_` prefix.
+pub const MAP_LUMPS: &[&str] = &[
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP", "BEHAVIOR", "SCRIPTS", "DIALOGUE", "ZNODES", "LEAFS", "LIGHTS", "MACROS",
+];
+
+/// First lump of a UDMF map, all lumps up to `UDMF_END` belong to the map
+const UDMF_START: &str = "TEXTMAP";
+const UDMF_END: &str = "ENDMAP";
+
+fn is_map_lump(name: &str) -> bool {
+    MAP_LUMPS.contains(&name) || name.starts_with("GL_")
+}
+
 pub enum LumpNode<'a> {
     Namespace {
         name: &'a str,
-        children: HashMap<&'a str, LumpNode<'a>>,
+        children: HashMap<Cow<'a, str>, LumpNode<'a>>,
     },
     Lump {
-        name: &'a str,
+        name: Cow<'a, str>,
         lump: LumpRef<'a>,
     },
 }
 
 impl<'a> LumpNode<'a> {
-    pub fn namespace(name: &'a str, children: HashMap<&'a str, LumpNode<'a>>) -> Self {
+    pub fn namespace(name: &'a str, children: HashMap<Cow<'a, str>, LumpNode<'a>>) -> Self {
         LumpNode::Namespace { name, children }
     }
 
-    pub fn lump(name: &'a str, lump: LumpRef<'a>) -> Self {
-        LumpNode::Lump { name, lump }
+    pub fn lump<N: Into<Cow<'a, str>>>(name: N, lump: LumpRef<'a>) -> Self {
+        LumpNode::Lump {
+            name: name.into(),
+            lump,
+        }
+    }
+}
+
+/// Typed lookups shared by every container that produces a `LumpNode` tree,
+/// so callers don't need to care whether a lump came from a WAD, a PK3 or a folder.
+pub trait LumpSource<'a> {
+    fn get_name(&self) -> &str;
+
+    fn get_lump_index(&self) -> &HashMap<Cow<'a, str>, LumpNode<'a>>;
+
+    fn get_lump(&self, namespaces: Vec<&str>, name: &str) -> Option<&LumpNode<'a>> {
+        let mut current_index = self.get_lump_index();
+        for namespace in namespaces {
+            if let Some(LumpNode::Namespace { children, .. }) = current_index.get(namespace) {
+                current_index = children;
+            } else {
+                return None;
+            }
+        }
+        current_index.get(name)
+    }
+
+    fn get_sound_sample(&self, name: &str) -> Result<Option<SoundSample>> {
+        if let Some(LumpNode::Lump { lump, .. }) = self.get_lump_index().get(name) {
+            Ok(Some(SoundSample::try_from(lump.decompressed()?.as_ref())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_maps(&self) -> Option<&HashMap<Cow<'a, str>, LumpNode<'a>>> {
+        if let Some(LumpNode::Namespace { children, .. }) = self.get_lump_index().get("MAPS") {
+            Some(children)
+        } else {
+            None
+        }
+    }
+}
+
+/// Merges the lump tree `other` into `target`.
+/// Namespaces present in both trees are merged recursively, lumps from `other`
+/// replace lumps with the same name, just like a later loaded PWAD would.
+pub(crate) fn merge_lump_index<'a>(
+    target: &mut HashMap<Cow<'a, str>, LumpNode<'a>>,
+    other: HashMap<Cow<'a, str>, LumpNode<'a>>,
+) {
+    for (name, node) in other {
+        match (target.get_mut(&name), node) {
+            (
+                Some(LumpNode::Namespace { children, .. }),
+                LumpNode::Namespace {
+                    children: other_children,
+                    ..
+                },
+            ) => merge_lump_index(children, other_children),
+            (_, node) => {
+                target.insert(name, node);
+            }
+        }
     }
 }
 
-pub fn index_tokens<'a>(tokens: TokenIterator<'a>) -> Result<HashMap<&'a str, LumpNode<'a>>> {
+pub fn index_tokens<'a>(tokens: TokenIterator<'a>) -> Result<HashMap<Cow<'a, str>, LumpNode<'a>>> {
     let mut tokens = tokens.peekable();
-    let mut lumps: HashMap<&'a str, LumpNode<'a>> = HashMap::new();
-    let mut maps: HashMap<&'a str, LumpNode<'a>> = HashMap::new();
+    let mut lumps: HashMap<Cow<'a, str>, LumpNode<'a>> = HashMap::new();
+    let mut maps: HashMap<Cow<'a, str>, LumpNode<'a>> = HashMap::new();
 
     while let Some(result) = tokens.next() {
         let token = result?;
         match token {
             LumpToken::Lump(name, lump_ref) => {
-                let lump_node = LumpNode::lump(name, lump_ref);
+                let lump_node = LumpNode::lump(name.clone(), lump_ref);
                 lumps.insert(name, lump_node);
             }
 
             LumpToken::MapMarker(name) => {
                 let map = index_map(name, &mut tokens)?;
-                maps.insert(name, map);
+                maps.insert(name.into(), map);
                 continue;
             }
 
             LumpToken::MarkerStart(marker) => {
                 let children = index_namespace(marker, &mut tokens)?;
                 let namespace_node = LumpNode::namespace(marker, children);
-                lumps.insert(marker, namespace_node);
+                lumps.insert(marker.into(), namespace_node);
             }
             LumpToken::MarkerEnd(_) => {
                 return Err("Unexpected end marker without matching start marker".into());
             }
         }
     }
-    lumps.insert("MAPS", LumpNode::namespace("MAPS", maps));
+    lumps.insert("MAPS".into(), LumpNode::namespace("MAPS", maps));
 
     Ok(lumps)
 }
 
 fn index_map<'a>(name: &'a str, tokens: &mut Peekable<TokenIterator<'a>>) -> Result<LumpNode<'a>> {
-    tokens.next();
-
     let mut map = HashMap::new();
+    let udmf = matches!(tokens.peek(), Some(Ok(LumpToken::Lump(name, ..))) if name == UDMF_START);
     while let Some(Ok(LumpToken::Lump(name, ..))) = tokens.peek() {
-        match *name {
-            "THINGS" | "LINEDEFS" | "SIDEDEFS" | "VERTEXES" | "SECTORS" | "SEGS" | "SSECTORS"
-            | "NODES" | "REJECT" | "BLOCKMAP" | "BEHAVIOR" => {
-                if let Some(Ok(LumpToken::Lump(name, lump_ref))) = tokens.next() {
-                    map.insert(name, LumpNode::lump(name, lump_ref));
-                }
-            }
-            _ => break,
+        if !udmf && !is_map_lump(name) {
+            break;
+        }
+        let end = udmf && name == UDMF_END;
+        if let Some(Ok(LumpToken::Lump(name, lump_ref))) = tokens.next() {
+            map.insert(name.clone(), LumpNode::lump(name, lump_ref));
+        }
+        if end {
+            break;
         }
     }
 
@@ -83,7 +163,7 @@ fn index_map<'a>(name: &'a str, tokens: &mut Peekable<TokenIterator<'a>>) -> Res
 fn index_namespace<'a>(
     namespace: &'a str,
     tokens: &mut Peekable<TokenIterator<'a>>,
-) -> Result<HashMap<&'a str, LumpNode<'a>>> {
+) -> Result<HashMap<Cow<'a, str>, LumpNode<'a>>> {
     let mut lumps = HashMap::new();
 
     while let Some(result) = tokens.next() {
@@ -91,12 +171,12 @@ fn index_namespace<'a>(
 
         match token {
             LumpToken::Lump(name, lump_ref) => {
-                lumps.insert(name, LumpNode::lump(name, lump_ref));
+                lumps.insert(name.clone(), LumpNode::lump(name, lump_ref));
             }
 
             LumpToken::MarkerStart(name) => {
                 let children = index_namespace(name, tokens)?;
-                lumps.insert(name, LumpNode::namespace(name, children));
+                lumps.insert(name.into(), LumpNode::namespace(name, children));
             }
 
             LumpToken::MarkerEnd(name) => {
@@ -129,7 +209,7 @@ fn index_namespace<'a>(
 // fn index_namespace<'a>(
 //     namespace: &'a str,
 //     tokens: &mut Peekable<TokenIterator>,
-// ) -> Result<HashMap<&'a str, LumpNode<'a>>> {
+// ) -> Result<HashMap<Cow<'a, str>, LumpNode<'a>>> {
 //     let mut lumps: HashMap<&str, LumpNode> = HashMap::new();
 //     tokens.next();
 //     while let Some(token) = tokens.peek() {
@@ -9,5 +9,27 @@ pub mod index;
 pub mod audio;
 pub mod graphics;
 pub mod sprite;
+pub mod sprite_set;
+pub mod png;
+pub mod picture;
+pub mod miptex;
+pub mod resource;
+pub mod pk3;
+pub mod folder;
+pub mod lzss;
+pub mod convert;
+pub mod colormap;
+pub mod translation;
+pub mod endoom;
+pub mod font;
+pub mod flat;
+pub mod raw_picture;
+pub mod startup;
+pub mod texture;
+pub mod animation;
+pub mod canvas;
+pub mod rgba;
+#[cfg(feature = "export")]
+pub mod export;
 
 pub use wad::WadIndex;
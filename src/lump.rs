@@ -1,5 +1,13 @@
+use crate::lzss;
+use std::borrow::Cow;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
 pub const LUMP_NAME_LENGTH: usize = 8;
 pub const LUMP_ENTRY_LENGTH: usize = 16;
+pub const TEXTURE_LUMP_NAME_LENGTH: usize = 16;
+pub const TEXTURE_LUMP_ENTRY_LENGTH: usize = 32;
 
 /// A refence to a lump data and it's name
 /// this struct does not own any data, it just points to offsets in the WAD file
@@ -13,30 +21,96 @@ pub const LUMP_ENTRY_LENGTH: usize = 16;
 /// - `start`: The start offset of the lump data in the WAD file
 /// - `end`: The end offset of the lump data in the WAD file
 /// - `name_offset`: The offset of the lump name in the WAD file
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LumpRef<'a> {
     data: &'a [u8],
-    name: &'a str,
+    name: Cow<'a, str>,
+    lump_type: u8,
+    size: usize,
+    compressed: bool,
+    entry_index: usize,
 }
 
 impl<'a> LumpRef<'a> {
     /// Creates a new LumpRef
-    pub fn new(data: &'a [u8], name: &'a str) -> Self {
-        Self { data, name }
+    pub fn new<N: Into<Cow<'a, str>>>(data: &'a [u8], name: N) -> Self {
+        Self::with_type(data, name, 0)
+    }
+
+    /// Creates a new LumpRef carrying the type byte of a WAD2/WAD3 directory entry
+    pub fn with_type<N: Into<Cow<'a, str>>>(data: &'a [u8], name: N, lump_type: u8) -> Self {
+        Self {
+            data,
+            name: name.into(),
+            lump_type,
+            size: data.len(),
+            compressed: false,
+            entry_index: 0,
+        }
+    }
+
+    /// Creates a new LumpRef for LZSS compressed data of Jaguar and Doom 64 WADs
+    /// `data` starts with the compressed stream, `size` is the decompressed size.
+    pub fn compressed<N: Into<Cow<'a, str>>>(data: &'a [u8], name: N, size: usize) -> Self {
+        Self {
+            data,
+            name: name.into(),
+            lump_type: 0,
+            size,
+            compressed: true,
+            entry_index: 0,
+        }
+    }
+
+    /// Sets the position of the lump in the WAD directory
+    pub fn with_entry_index(mut self, entry_index: usize) -> Self {
+        self.entry_index = entry_index;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn name(&self) -> &'a str {
-        self.name
+    /// Type byte of a WAD2/WAD3 directory entry, always 0 for IWAD/PWAD lumps
+    pub fn lump_type(&self) -> u8 {
+        self.lump_type
     }
 
     pub fn is_marker(&self) -> bool {
         self.data.len() == 0
     }
 
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Position of the lump in the WAD directory, keeps the original order of lumps which
+    /// are grouped by name in the index (e.g. map lumps). 0 for lumps of path based containers.
+    pub fn entry_index(&self) -> usize {
+        self.entry_index
+    }
+
+    /// Size of the lump content, after decompression for compressed lumps
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     // Extracts the lump content from the provided data
+    // for compressed lumps this is the raw compressed stream, see `decompressed()`
     pub fn data(&self) -> &'a [u8] {
         self.data
     }
+
+    /// Returns the lump content, decompressing it first if necessary.
+    /// Uncompressed lumps are borrowed without copying.
+    pub fn decompressed(&self) -> Result<Cow<'a, [u8]>> {
+        if self.compressed {
+            Ok(Cow::Owned(lzss::decompress(self.data, self.size)?))
+        } else {
+            Ok(Cow::Borrowed(self.data))
+        }
+    }
 }
 
 // #[cfg(test)]
@@ -0,0 +1,96 @@
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Decompresses a lump of a Jaguar or Doom 64 WAD.
+///
+/// # Format Description
+/// The data is a sequence of groups, each introduced by a flag byte which describes the
+/// following 8 items, starting with the least significant bit:
+/// - Bit clear: a literal byte which is copied to the output.
+/// - Bit set: a 2 byte back reference. The first byte and the high nibble of the second
+///   byte form a 12 bit distance `d`, the low nibble is the length `l`. `l + 1` bytes are
+///   copied starting `d + 1` bytes before the end of the output. A length of 1 (`l == 0`)
+///   marks the end of the data.
+///
+/// # Arguments
+/// - `data`: The compressed lump data.
+/// - `size`: The decompressed size from the directory entry.
+/// # Returns
+/// - `Ok(Vec<u8>)` with exactly `size` bytes.
+/// - `Err` if the data ends early or references data before the start of the output.
+pub fn decompress(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(size);
+    let mut input = data.iter().copied();
+    let mut next = || input.next().ok_or("unexpected end of compressed data");
+
+    'groups: while output.len() < size {
+        let flags = next()?;
+        for bit in 0..8 {
+            if output.len() >= size {
+                break 'groups;
+            }
+
+            if flags & (1 << bit) == 0 {
+                output.push(next()?);
+                continue;
+            }
+
+            let high = next()? as usize;
+            let low = next()? as usize;
+            let distance = (high << 4 | low >> 4) + 1;
+            let length = (low & 0x0F) + 1;
+            if length == 1 {
+                break 'groups;
+            }
+
+            let start = output
+                .len()
+                .checked_sub(distance)
+                .ok_or("back reference before start of data")?;
+            // the source may overlap the bytes being written, copy one at a time
+            for i in 0..length {
+                let byte = output[start + i];
+                output.push(byte);
+            }
+        }
+    }
+
+    if output.len() < size {
+        return Err("compressed data shorter than lump size".into());
+    }
+    output.truncate(size);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_copies_literals() {
+        let data = [0b0000_0000, b'D', b'O', b'O', b'M', 0, 0, 0, 0];
+        let output = decompress(&data, 4).unwrap();
+        assert_eq!(output, b"DOOM");
+    }
+
+    #[test]
+    fn decompress_resolves_overlapping_back_references() {
+        // 'A', 'B', then copy 6 bytes starting 2 bytes back, then end marker
+        let data = [0b0000_1100, b'A', b'B', 0x00, 0x15, 0x00, 0x00];
+        let output = decompress(&data, 8).unwrap();
+        assert_eq!(output, b"ABABABAB");
+    }
+
+    #[test]
+    fn decompress_rejects_back_reference_before_start() {
+        let data = [0b0000_0001, 0x01, 0x03];
+        assert!(decompress(&data, 4).is_err());
+    }
+
+    #[test]
+    fn decompress_rejects_truncated_data() {
+        let data = [0b0000_0000, b'D', b'O'];
+        assert!(decompress(&data, 4).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::graphics::Palette;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+const HEADER_SIZE: usize = 40;
+const NAME_LENGTH: usize = 16;
+pub const MIP_LEVELS: usize = 4;
+
+/// Lump type of a palette lump in WAD2/WAD3 directories ('@')
+pub const LUMP_TYPE_PALETTE: u8 = 0x40;
+/// Lump type of a status bar picture in WAD2/WAD3 directories ('B')
+pub const LUMP_TYPE_QPIC: u8 = 0x42;
+/// Lump type of a mip texture in WAD3 directories ('C')
+pub const LUMP_TYPE_WAD3_MIPTEX: u8 = 0x43;
+/// Lump type of a mip texture in WAD2 directories ('D')
+pub const LUMP_TYPE_WAD2_MIPTEX: u8 = 0x44;
+
+/// Represents a Quake (WAD2) or Half-Life (WAD3) mip texture lump.
+///
+/// # Structure of a Mip Texture Lump
+/// A mip texture lump starts with a 40-byte header:
+/// - Bytes 0-15: Zero terminated texture name
+/// - Bytes 16-19: Width (u32, little-endian)
+/// - Bytes 20-23: Height (u32, little-endian)
+/// - Bytes 24-39: Four offsets (u32, little-endian) to the pixel data of each mip level,
+///   relative to the start of the lump
+///
+/// Each mip level halves the dimensions of the previous one and stores one palette
+/// index per pixel. WAD3 textures are followed by an embedded palette:
+/// - 2 bytes: Number of colors (u16, little-endian, always 256)
+/// - N * 3 bytes: RGB colors
+///
+/// WAD2 textures have no embedded palette and are decoded with the Quake palette.
+#[derive(Debug, Clone)]
+pub struct MipTexture<'a> {
+    lump_data: &'a [u8],
+    name: &'a str,
+    width: u32,
+    height: u32,
+    offsets: [u32; MIP_LEVELS],
+}
+
+impl<'a> MipTexture<'a> {
+    /// Creates a `MipTexture` from the complete lump slice.
+    ///
+    /// # Arguments
+    /// - `lump_data`: The data of the mip texture lump.
+    /// # Returns
+    /// - `Ok(MipTexture)` if the header is valid.
+    /// - `Err` if the lump is too small or the name is not valid UTF-8.
+    pub fn new(lump_data: &'a [u8]) -> Result<Self> {
+        if lump_data.len() < HEADER_SIZE {
+            return Err("Mip texture lump too small for header".into());
+        }
+
+        let name_bytes = &lump_data[..NAME_LENGTH];
+        let name_len = name_bytes
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(NAME_LENGTH);
+        let name = std::str::from_utf8(&name_bytes[..name_len])?;
+
+        let read_u32 = |at: usize| {
+            u32::from_le_bytes([
+                lump_data[at],
+                lump_data[at + 1],
+                lump_data[at + 2],
+                lump_data[at + 3],
+            ])
+        };
+        let width = read_u32(16);
+        let height = read_u32(20);
+        let mut offsets = [0u32; MIP_LEVELS];
+        for (level, offset) in offsets.iter_mut().enumerate() {
+            *offset = read_u32(24 + level * 4);
+        }
+
+        Ok(Self {
+            lump_data,
+            name,
+            width,
+            height,
+            offsets,
+        })
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Half-Life marks textures with transparent holes with a leading '{',
+    /// for those the last palette index (255) is fully transparent.
+    pub fn is_transparent(&self) -> bool {
+        self.name.starts_with('{')
+    }
+
+    /// Returns the palette indices of the given mip level.
+    ///
+    /// # Arguments
+    /// - `level`: The mip level, 0 is the full size texture, each level halves the dimensions.
+    /// # Returns
+    /// - `Ok(&[u8])` with `(width >> level) * (height >> level)` palette indices.
+    /// - `Err` if the level does not exist or the pixel data is out of range.
+    pub fn mip_level(&self, level: usize) -> Result<&'a [u8]> {
+        let offset = *self.offsets.get(level).ok_or("mip level out of range")? as usize;
+        if offset == 0 {
+            return Err("mip texture has no pixel data in this lump".into());
+        }
+
+        let size = ((self.width >> level) as usize)
+            .checked_mul((self.height >> level) as usize)
+            .ok_or("mip level size overflow")?;
+
+        self.lump_data
+            .get(offset..offset + size)
+            .ok_or_else(|| "mip level data out of range".into())
+    }
+
+    /// Returns the palette embedded after the last mip level of WAD3 textures.
+    ///
+    /// # Returns
+    /// - `Some(Palette)` for WAD3 textures.
+    /// - `None` if the lump has no embedded palette (WAD2 textures).
+    pub fn palette(&self) -> Option<Palette<'a>> {
+        let last = MIP_LEVELS - 1;
+        let last_size = (self.width >> last) as usize * (self.height >> last) as usize;
+        let palette_offset = (self.offsets[last] as usize).checked_add(last_size)?;
+        let count = self.lump_data.get(palette_offset..palette_offset + 2)?;
+        if u16::from_le_bytes([count[0], count[1]]) != 256 {
+            return None;
+        }
+
+        Palette::from_bytes(self.lump_data.get(palette_offset + 2..)?).ok()
+    }
+
+    /// Converts the full size texture into an RGBA pixel buffer.
+    ///
+    /// # Arguments
+    /// - `palette`: The embedded palette for WAD3 textures or the Quake palette for WAD2 textures.
+    /// # Returns
+    /// - `Ok(Vec<u8>)` with `width * height * 4` bytes.
+    /// - `Err` if the texture has no pixel data or a zero dimension.
+    pub fn rgba_pixel_buffer(&self, palette: &Palette) -> Result<Vec<u8>> {
+        if self.width == 0 || self.height == 0 {
+            return Err("mip texture has zero width or height".into());
+        }
+
+        let pixels = self.mip_level(0)?;
+        let transparent = self.is_transparent();
+        let mut pixel_buffer = vec![0u8; pixels.len() * 4];
+
+        for (&index, rgba) in pixels.iter().zip(pixel_buffer.chunks_exact_mut(4)) {
+            if transparent && index == 255 {
+                continue;
+            }
+            rgba.copy_from_slice(
+                palette
+                    .get_rgba(index as usize)
+                    .ok_or("palette index out of bounds")?
+                    .as_ref(),
+            );
+        }
+
+        Ok(pixel_buffer)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for MipTexture<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        MipTexture::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a 16x16 WAD3 texture filled with the given index and an optional palette
+    fn texture_lump(name: &[u8], fill: u8, with_palette: bool) -> Vec<u8> {
+        let mut data = vec![0u8; NAME_LENGTH];
+        data[..name.len()].copy_from_slice(name);
+        data.extend(&16u32.to_le_bytes());
+        data.extend(&16u32.to_le_bytes());
+        let mut offset = HEADER_SIZE as u32;
+        for level in 0..MIP_LEVELS {
+            data.extend(&offset.to_le_bytes());
+            offset += (16 >> level) * (16 >> level);
+        }
+        for level in 0..MIP_LEVELS {
+            data.extend(vec![fill; (16 >> level) * (16 >> level)]);
+        }
+        if with_palette {
+            data.extend(&256u16.to_le_bytes());
+            data.extend((0..768).map(|val: u16| (val % 256) as u8));
+            data.extend(&[0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn mip_texture_rejects_too_small_data() {
+        let result = MipTexture::new(&[0u8; 20]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn mip_texture_can_extract_header_data() {
+        let data = texture_lump(b"BRICK01", 1, false);
+        let texture = MipTexture::new(&data).unwrap();
+        assert_eq!(texture.name(), "BRICK01");
+        assert_eq!(texture.width(), 16);
+        assert_eq!(texture.height(), 16);
+        assert_eq!(texture.mip_level(0).unwrap().len(), 256);
+        assert_eq!(texture.mip_level(3).unwrap().len(), 4);
+        assert!(texture.mip_level(4).is_err());
+    }
+
+    #[test]
+    fn mip_texture_without_embedded_palette_has_none() {
+        let data = texture_lump(b"BRICK01", 1, false);
+        let texture = MipTexture::new(&data).unwrap();
+        assert!(texture.palette().is_none());
+    }
+
+    #[test]
+    fn mip_texture_can_convert_with_embedded_palette() {
+        let data = texture_lump(b"BRICK01", 1, true);
+        let texture = MipTexture::new(&data).unwrap();
+        let palette = texture.palette().unwrap();
+        let rgba = texture.rgba_pixel_buffer(&palette).unwrap();
+        assert_eq!(rgba.len(), 16 * 16 * 4);
+        assert_eq!(&rgba[0..4], &[3, 4, 5, 255]);
+    }
+
+    #[test]
+    fn mip_texture_treats_last_index_as_transparent_for_masked_textures() {
+        let data = texture_lump(b"{FENCE", 255, true);
+        let texture = MipTexture::new(&data).unwrap();
+        let palette = texture.palette().unwrap();
+        let rgba = texture.rgba_pixel_buffer(&palette).unwrap();
+        assert!(rgba.iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::flat::Flat;
+use crate::graphics::Palette;
+use crate::png::{PngImage, is_png};
+use crate::raw_picture::{RawPicture, is_raw_picture};
+use crate::sprite::Sprite;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// A picture lump in one of the formats used for sprites, patches, flats and fullscreen graphics.
+///
+/// PNG lumps are detected by their signature, so callers don't need to know how a PWAD
+/// stores its graphics. Offsets of PNG images come from the grAb chunk.
+#[derive(Debug, Clone)]
+pub enum Picture<'a> {
+    Patch(Sprite<'a>),
+    Flat(Flat<'a>),
+    Png(PngImage),
+    Raw(RawPicture<'a>),
+}
+
+impl<'a> Picture<'a> {
+    /// Decodes a sprite or patch lump, either a Doom patch or a PNG image.
+    ///
+    /// # Arguments
+    /// - `lump_data`: The data of the picture lump.
+    /// # Returns
+    /// - `Ok(Picture)` with the decoded picture.
+    /// - `Err` if the lump is neither a valid PNG image nor a valid patch.
+    pub fn from_patch_lump(lump_data: &'a [u8]) -> Result<Self> {
+        if is_png(lump_data) {
+            Ok(Picture::Png(PngImage::from_bytes(lump_data)?))
+        } else {
+            Ok(Picture::Patch(Sprite::new(lump_data)?))
+        }
+    }
+
+    /// Decodes a graphic lump outside of the flat namespaces, e.g. a title screen or menu
+    /// graphic, as PNG image, raw fullscreen picture or Doom patch.
+    ///
+    /// # Arguments
+    /// - `name`: The lump name, used to tell raw pictures apart from patches.
+    /// - `lump_data`: The data of the picture lump.
+    /// # Returns
+    /// - `Ok(Picture)` with the decoded picture.
+    /// - `Err` if the lump is none of the formats.
+    pub fn from_graphic_lump(name: &str, lump_data: &'a [u8]) -> Result<Self> {
+        if is_png(lump_data) {
+            Ok(Picture::Png(PngImage::from_bytes(lump_data)?))
+        } else if is_raw_picture(name, lump_data) {
+            Ok(Picture::Raw(RawPicture::new(lump_data)?))
+        } else {
+            Self::from_patch_lump(lump_data)
+        }
+    }
+
+    /// Decodes a flat lump, either a raw flat or a PNG image.
+    pub fn from_flat_lump(lump_data: &'a [u8]) -> Result<Self> {
+        if is_png(lump_data) {
+            Ok(Picture::Png(PngImage::from_bytes(lump_data)?))
+        } else {
+            Ok(Picture::Flat(Flat::new(lump_data)?))
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            Picture::Patch(patch) => patch.width() as u32,
+            Picture::Flat(flat) => flat.width() as u32,
+            Picture::Png(png) => png.width(),
+            Picture::Raw(raw) => raw.width() as u32,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            Picture::Patch(patch) => patch.height() as u32,
+            Picture::Flat(flat) => flat.height() as u32,
+            Picture::Png(png) => png.height(),
+            Picture::Raw(raw) => raw.height() as u32,
+        }
+    }
+
+    /// Horizontal offset of the picture, flats and raw pictures have none
+    pub fn left_offset(&self) -> i32 {
+        match self {
+            Picture::Patch(patch) => patch.left_offset() as i32,
+            Picture::Flat(_) | Picture::Raw(_) => 0,
+            Picture::Png(png) => png.left_offset(),
+        }
+    }
+
+    /// Vertical offset of the picture, flats and raw pictures have none
+    pub fn top_offset(&self) -> i32 {
+        match self {
+            Picture::Patch(patch) => patch.top_offset() as i32,
+            Picture::Flat(_) | Picture::Raw(_) => 0,
+            Picture::Png(png) => png.top_offset(),
+        }
+    }
+
+    /// Converts the picture into an RGBA pixel buffer with `width * height * 4` bytes.
+    /// PNG images carry their own colors, the palette is only used for Doom formats.
+    pub fn rgba_pixel_buffer(&self, palette: &Palette) -> Result<Vec<u8>> {
+        match self {
+            Picture::Patch(patch) => patch.rgba_pixel_buffer(palette),
+            Picture::Flat(flat) => flat.rgba_pixel_buffer(palette),
+            Picture::Png(png) => Ok(png.rgba().to_vec()),
+            Picture::Raw(raw) => raw.rgba_pixel_buffer(palette),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1x1 RGBA PNG with a grAb chunk, built with the chunk layout of the PNG spec
+    fn png_with_offsets() -> Vec<u8> {
+        let mut data = crate::png::PNG_SIGNATURE.to_vec();
+        let mut chunk = |chunk_type: &[u8; 4], content: &[u8]| {
+            data.extend(&(content.len() as u32).to_be_bytes());
+            let mut typed = chunk_type.to_vec();
+            typed.extend(content);
+            data.extend(&typed);
+            data.extend(&crc32fast::hash(&typed).to_be_bytes());
+        };
+        chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        chunk(b"grAb", &[0, 0, 0, 5, 0xFF, 0xFF, 0xFF, 0xFE]);
+        let pixels = miniz_oxide::deflate::compress_to_vec_zlib(&[0, 1, 2, 3, 255], 6);
+        chunk(b"IDAT", &pixels);
+        chunk(b"IEND", &[]);
+        data
+    }
+
+    #[test]
+    fn picture_detects_png_lumps() {
+        let data = png_with_offsets();
+        assert!(Sprite::new(&data).is_err());
+
+        let picture = Picture::from_patch_lump(&data).unwrap();
+        assert!(matches!(picture, Picture::Png(_)));
+        assert_eq!((picture.width(), picture.height()), (1, 1));
+        assert_eq!((picture.left_offset(), picture.top_offset()), (5, -2));
+
+        let palette_data = [0u8; 768];
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        assert_eq!(
+            picture.rgba_pixel_buffer(&palette).unwrap(),
+            vec![1, 2, 3, 255]
+        );
+
+        // a PNG of the size of a raw picture is still a PNG, even with a raw picture name
+        let mut padded = data.clone();
+        padded.resize(64000, 0);
+        for name in ["TITLE", "M_DOOM"] {
+            let picture = Picture::from_graphic_lump(name, &padded).unwrap();
+            assert!(matches!(picture, Picture::Png(_)));
+        }
+    }
+
+    #[test]
+    fn picture_decodes_doom_formats() {
+        let patch = [1, 0, 1, 0, 3, 0, 4, 0, 12, 0, 0, 0, 0, 1, 0, 5, 0, 0xFF];
+        let picture = Picture::from_patch_lump(&patch).unwrap();
+        assert!(matches!(picture, Picture::Patch(_)));
+        assert_eq!((picture.left_offset(), picture.top_offset()), (3, 4));
+
+        let raw = vec![0u8; 64000];
+        let picture = Picture::from_graphic_lump("CREDIT", &raw).unwrap();
+        assert!(matches!(picture, Picture::Raw(_)));
+        assert_eq!((picture.width(), picture.height()), (320, 200));
+        assert!(matches!(
+            Picture::from_graphic_lump("M_DOOM", &patch).unwrap(),
+            Picture::Patch(_)
+        ));
+
+        let flat = [0u8; 4096];
+        let picture = Picture::from_flat_lump(&flat).unwrap();
+        assert!(matches!(picture, Picture::Flat(_)));
+        assert_eq!((picture.width(), picture.height()), (64, 64));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::resource::{ResourceFile, ResourceIndex};
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_ENTRY_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+const MAX_COMMENT_LENGTH: usize = u16::MAX as usize;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16> {
+    let bytes = data.get(at..at + 2).ok_or("Unexpected end of ZIP data")?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    let bytes = data.get(at..at + 4).ok_or("Unexpected end of ZIP data")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// A PK3 (ZIP) resource archive as used by modern Doom source ports.
+///
+/// Reads all files of the archive into memory (inflating deflated entries), the lump tree
+/// is then built with `index()` and borrows from the archive like `WadIndex` borrows from
+/// the WAD data.
+///
+/// # Supported ZIP Features
+/// - Stored (method 0) and deflated (method 8) entries
+/// - CRC-32 verification of every entry
+///
+/// Encrypted entries, ZIP64 archives and multi disk archives are rejected.
+#[derive(Debug, Clone)]
+pub struct Pk3Archive {
+    name: String,
+    files: Vec<ResourceFile>,
+}
+
+impl Pk3Archive {
+    /// Reads a PK3 archive from its raw bytes.
+    ///
+    /// # Arguments
+    /// - `name`: The name of the archive, usually the file name.
+    /// - `data`: The complete archive data.
+    /// # Returns
+    /// - `Ok(Pk3Archive)` with every file of the archive.
+    /// - `Err` if the data is not a valid ZIP archive or uses unsupported features.
+    pub fn from_bytes(name: String, data: &[u8]) -> Result<Self> {
+        let eocd = Self::find_end_of_central_directory(data)?;
+        let entry_count = read_u16(data, eocd + 10)? as usize;
+        let directory_offset = read_u32(data, eocd + 16)? as usize;
+        if read_u16(data, eocd + 4)? != 0 || read_u16(data, eocd + 6)? != 0 {
+            return Err("Multi disk ZIP archives are not supported".into());
+        }
+        if directory_offset == u32::MAX as usize {
+            return Err("ZIP64 archives are not supported".into());
+        }
+
+        let mut files = Vec::with_capacity(entry_count);
+        let mut cursor = directory_offset;
+        for _ in 0..entry_count {
+            if read_u32(data, cursor)? != CENTRAL_DIRECTORY_SIGNATURE {
+                return Err("Invalid ZIP central directory entry".into());
+            }
+
+            let flags = read_u16(data, cursor + 8)?;
+            let method = read_u16(data, cursor + 10)?;
+            let crc = read_u32(data, cursor + 16)?;
+            let compressed_size = read_u32(data, cursor + 20)? as usize;
+            let size = read_u32(data, cursor + 24)? as usize;
+            let name_length = read_u16(data, cursor + 28)? as usize;
+            let extra_length = read_u16(data, cursor + 30)? as usize;
+            let comment_length = read_u16(data, cursor + 32)? as usize;
+            let local_offset = read_u32(data, cursor + 42)? as usize;
+
+            let name_start = cursor + CENTRAL_DIRECTORY_ENTRY_SIZE;
+            let path_bytes = data
+                .get(name_start..name_start + name_length)
+                .ok_or("ZIP entry name out of range")?;
+            let path = String::from_utf8_lossy(path_bytes).replace('\\', "/");
+            cursor = name_start + name_length + extra_length + comment_length;
+
+            // folders have no content
+            if path.ends_with('/') {
+                continue;
+            }
+            if flags & FLAG_ENCRYPTED != 0 {
+                return Err(format!("ZIP entry '{}' is encrypted", path).into());
+            }
+
+            let stored = Self::local_data(data, local_offset, compressed_size)?;
+            let content = match method {
+                METHOD_STORED => stored.to_vec(),
+                METHOD_DEFLATED => miniz_oxide::inflate::decompress_to_vec_with_limit(stored, size)
+                    .map_err(|e| format!("Failed to inflate ZIP entry '{}': {}", path, e))?,
+                _ => {
+                    return Err(format!(
+                        "ZIP entry '{}' uses unsupported compression method {}",
+                        path, method
+                    )
+                    .into());
+                }
+            };
+
+            if content.len() != size || crc32fast::hash(&content) != crc {
+                return Err(format!("ZIP entry '{}' is corrupt", path).into());
+            }
+
+            // files like ".gitkeep" have no lump name and can't be addressed
+            if let Ok(file) = ResourceFile::new(path, content) {
+                files.push(file);
+            }
+        }
+
+        Ok(Self { name, files })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// All files of the archive in central directory order
+    pub fn files(&self) -> &[ResourceFile] {
+        &self.files
+    }
+
+    /// Builds the lump tree of the archive, embedded WADs are indexed recursively.
+    pub fn index(&self) -> Result<ResourceIndex<'_>> {
+        ResourceIndex::from_files(self.name.clone(), &self.files)
+    }
+
+    /// The end of central directory record sits at the end of the archive,
+    /// followed only by an optional comment of up to 65535 bytes.
+    fn find_end_of_central_directory(data: &[u8]) -> Result<usize> {
+        if data.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+            return Err("Data too small to contain a ZIP archive".into());
+        }
+
+        let last = data.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+        let first = last.saturating_sub(MAX_COMMENT_LENGTH);
+        (first..=last)
+            .rev()
+            .find(|&at| read_u32(data, at).ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+            .ok_or_else(|| "ZIP end of central directory not found".into())
+    }
+
+    fn local_data(data: &[u8], local_offset: usize, compressed_size: usize) -> Result<&[u8]> {
+        if read_u32(data, local_offset)? != LOCAL_HEADER_SIGNATURE {
+            return Err("Invalid ZIP local file header".into());
+        }
+
+        let name_length = read_u16(data, local_offset + 26)? as usize;
+        let extra_length = read_u16(data, local_offset + 28)? as usize;
+        let start = local_offset + LOCAL_HEADER_SIZE + name_length + extra_length;
+        data.get(start..start + compressed_size)
+            .ok_or_else(|| "ZIP entry data out of range".into())
+    }
+}
+
+/// Builds a PK3 (ZIP) archive in memory.
+///
+/// Entries are deflated when that makes them smaller and stored otherwise. All entries get
+/// the same fixed timestamp (1980-01-01 00:00), so the same input always produces the same
+/// archive.
+#[derive(Debug, Clone, Default)]
+pub struct Pk3Writer {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entry_count: usize,
+}
+
+impl Pk3Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a file to the archive.
+    ///
+    /// # Arguments
+    /// - `path`: The path of the file inside the archive, separated by '/'.
+    /// - `content`: The uncompressed file content.
+    /// # Returns
+    /// - `Ok(())` if the file was added.
+    /// - `Err` if the archive would exceed the limits of a non ZIP64 archive.
+    pub fn add_file(&mut self, path: &str, content: &[u8]) -> Result<()> {
+        if self.entry_count >= u16::MAX as usize {
+            return Err("Too many entries for a ZIP archive".into());
+        }
+        if path.is_empty() || path.len() > u16::MAX as usize {
+            return Err(format!("Invalid ZIP entry path '{}'", path).into());
+        }
+
+        let deflated = miniz_oxide::deflate::compress_to_vec(content, 6);
+        let (method, stored) = if deflated.len() < content.len() {
+            (METHOD_DEFLATED, deflated.as_slice())
+        } else {
+            (METHOD_STORED, content)
+        };
+        let local_offset = self.data.len();
+        if content.len() >= u32::MAX as usize
+            || local_offset + LOCAL_HEADER_SIZE + path.len() + stored.len() >= u32::MAX as usize
+        {
+            return Err("ZIP archive too large".into());
+        }
+
+        let mut fields: Vec<u8> = Vec::with_capacity(LOCAL_HEADER_SIZE - 4);
+        fields.extend(&20u16.to_le_bytes()); // version needed
+        fields.extend(&0u16.to_le_bytes()); // flags
+        fields.extend(&method.to_le_bytes());
+        fields.extend(&0u16.to_le_bytes()); // time 00:00:00
+        fields.extend(&0x0021u16.to_le_bytes()); // date 1980-01-01
+        fields.extend(&crc32fast::hash(content).to_le_bytes());
+        fields.extend(&(stored.len() as u32).to_le_bytes());
+        fields.extend(&(content.len() as u32).to_le_bytes());
+        fields.extend(&(path.len() as u16).to_le_bytes());
+        fields.extend(&0u16.to_le_bytes()); // extra length
+
+        self.directory
+            .extend(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        self.directory.extend(&20u16.to_le_bytes()); // version made by
+        self.directory.extend(&fields);
+        self.directory.extend(&[0; 6]); // comment length, disk, internal attributes
+        self.directory.extend(&[0; 4]); // external attributes
+        self.directory.extend(&(local_offset as u32).to_le_bytes());
+        self.directory.extend(path.as_bytes());
+
+        self.data.extend(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        self.data.extend(&fields);
+        self.data.extend(path.as_bytes());
+        self.data.extend(stored);
+        self.entry_count += 1;
+
+        Ok(())
+    }
+
+    /// Appends the central directory and returns the complete archive
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let directory_offset = self.data.len();
+        if directory_offset + self.directory.len() >= u32::MAX as usize {
+            return Err("ZIP archive too large".into());
+        }
+
+        let directory_size = self.directory.len() as u32;
+        self.data.extend(self.directory);
+        self.data
+            .extend(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        self.data.extend(&[0; 4]); // disk numbers
+        self.data.extend(&(self.entry_count as u16).to_le_bytes());
+        self.data.extend(&(self.entry_count as u16).to_le_bytes());
+        self.data.extend(&directory_size.to_le_bytes());
+        self.data.extend(&(directory_offset as u32).to_le_bytes());
+        self.data.extend(&0u16.to_le_bytes()); // comment length
+
+        Ok(self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{LumpNode, LumpSource};
+
+    /// Builds a ZIP archive from (path, content, deflate) triples
+    fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut directory: Vec<u8> = Vec::new();
+        for (path, content, deflate) in entries {
+            let stored = if *deflate {
+                miniz_oxide::deflate::compress_to_vec(content, 6)
+            } else {
+                content.to_vec()
+            };
+            let method = if *deflate {
+                METHOD_DEFLATED
+            } else {
+                METHOD_STORED
+            };
+            let mut fields: Vec<u8> = Vec::new();
+            fields.extend(&20u16.to_le_bytes()); // version needed
+            fields.extend(&0u16.to_le_bytes()); // flags
+            fields.extend(&method.to_le_bytes());
+            fields.extend(&[0, 0, 0, 0]); // time, date
+            fields.extend(&crc32fast::hash(content).to_le_bytes());
+            fields.extend(&(stored.len() as u32).to_le_bytes());
+            fields.extend(&(content.len() as u32).to_le_bytes());
+            fields.extend(&(path.len() as u16).to_le_bytes());
+            fields.extend(&0u16.to_le_bytes()); // extra length
+
+            directory.extend(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+            directory.extend(&20u16.to_le_bytes()); // version made by
+            directory.extend(&fields);
+            directory.extend(&[0; 6]); // comment length, disk, internal attributes
+            directory.extend(&[0; 4]); // external attributes
+            directory.extend(&(data.len() as u32).to_le_bytes());
+            directory.extend(path.as_bytes());
+
+            data.extend(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            data.extend(&fields);
+            data.extend(path.as_bytes());
+            data.extend(&stored);
+        }
+        let directory_offset = data.len() as u32;
+        let directory_size = directory.len() as u32;
+        data.extend(directory);
+        data.extend(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        data.extend(&[0; 4]); // disk numbers
+        data.extend(&(entries.len() as u16).to_le_bytes());
+        data.extend(&(entries.len() as u16).to_le_bytes());
+        data.extend(&directory_size.to_le_bytes());
+        data.extend(&directory_offset.to_le_bytes());
+        data.extend(&0u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn pk3_archive_rejects_invalid_data() {
+        let result = Pk3Archive::from_bytes("test.pk3".to_string(), &[0u8; 64]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn pk3_archive_reads_stored_and_deflated_files() {
+        let data = zip(&[
+            ("sprites/", b"", false),
+            ("sprites/trooa1.lmp", &[1, 2, 3], false),
+            ("music/d_runnin.mid", &[7; 100], true),
+        ]);
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &data).unwrap();
+
+        assert_eq!(archive.files().len(), 2);
+        assert_eq!(archive.files()[0].name(), "TROOA1");
+        assert_eq!(archive.files()[1].data(), &[7; 100]);
+    }
+
+    #[test]
+    fn pk3_archive_detects_corrupt_entries() {
+        let mut data = zip(&[("decorate.txt", b"actor", false)]);
+        data[LOCAL_HEADER_SIZE + "decorate.txt".len()] = b'A';
+        let result = Pk3Archive::from_bytes("test.pk3".to_string(), &data);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn pk3_archive_can_be_indexed() {
+        let data = zip(&[
+            ("sprites/trooa1.lmp", &[1, 2, 3], true),
+            ("sounds/dspistol.lmp", &[4, 5, 6], false),
+        ]);
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &data).unwrap();
+        let index = archive.index().unwrap();
+
+        match index.get_lump(vec!["S_START"], "TROOA1").unwrap() {
+            LumpNode::Lump { lump, .. } => assert_eq!(lump.data(), &[1, 2, 3]),
+            _ => panic!("TROOA1 is not a lump"),
+        }
+        assert!(index.get_lump(vec![], "DSPISTOL").is_some());
+        assert!(index.get_maps().unwrap().is_empty());
+    }
+
+    #[test]
+    fn pk3_writer_output_can_be_read_back() {
+        let mut writer = Pk3Writer::new();
+        writer.add_file("sprites/trooa1.lmp", &[1, 2, 3]).unwrap();
+        writer.add_file("music/d_runnin.mid", &[7; 100]).unwrap();
+        let data = writer.finish().unwrap();
+
+        let archive = Pk3Archive::from_bytes("test.pk3".to_string(), &data).unwrap();
+        assert_eq!(archive.files().len(), 2);
+        assert_eq!(archive.files()[0].path(), "sprites/trooa1.lmp");
+        assert_eq!(archive.files()[0].data(), &[1, 2, 3]);
+        assert_eq!(archive.files()[1].data(), &[7; 100]);
+        // the tiny sprite is stored, the repeated bytes are deflated
+        let second = LOCAL_HEADER_SIZE + "sprites/trooa1.lmp".len() + 3;
+        assert_eq!(read_u16(&data, 8).unwrap(), METHOD_STORED);
+        assert_eq!(read_u16(&data, second + 8).unwrap(), METHOD_DEFLATED);
+    }
+}
@@ -0,0 +1,680 @@
+use crate::graphics::{IndexedImage, Palette};
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+const COLOR_GRAY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GRAY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+/// Pixels with a lower alpha value are transparent when mapped to palette indices
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Start column, start row, column step and row step of the 7 Adam7 passes
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Checks if the lump data starts with the PNG signature
+pub fn is_png(data: &[u8]) -> bool {
+    data.starts_with(&PNG_SIGNATURE)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    let bytes = data.get(at..at + 4).ok_or("Unexpected end of PNG data")?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Fields of the IHDR chunk
+#[derive(Debug, Clone, Copy)]
+struct ImageHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl ImageHeader {
+    fn from_chunk(chunk: &[u8]) -> Result<Self> {
+        if chunk.len() != 13 {
+            return Err("Invalid PNG IHDR chunk".into());
+        }
+
+        let header = Self {
+            width: read_u32(chunk, 0)? as usize,
+            height: read_u32(chunk, 4)? as usize,
+            bit_depth: chunk[8],
+            color_type: chunk[9],
+            interlaced: chunk[12] == 1,
+        };
+        let valid_depth = match header.color_type {
+            COLOR_GRAY => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+            COLOR_PALETTE => matches!(header.bit_depth, 1 | 2 | 4 | 8),
+            COLOR_RGB | COLOR_GRAY_ALPHA | COLOR_RGBA => matches!(header.bit_depth, 8 | 16),
+            _ => false,
+        };
+        if !valid_depth || chunk[10] != 0 || chunk[11] != 0 || chunk[12] > 1 {
+            return Err("Unsupported PNG color type, bit depth or method".into());
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err("PNG image has zero width or height".into());
+        }
+
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_RGB => 3,
+            COLOR_GRAY_ALPHA => 2,
+            COLOR_RGBA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// The sub images the scanlines are stored in, as (x0, y0, dx, dy)
+    fn passes(&self) -> &'static [(usize, usize, usize, usize)] {
+        if self.interlaced {
+            &ADAM7_PASSES
+        } else {
+            &[(0, 0, 1, 1)]
+        }
+    }
+
+    /// Returns the columns, rows and bytes per scanline of a pass
+    fn pass_size(&self, (x0, y0, dx, dy): (usize, usize, usize, usize)) -> (usize, usize, usize) {
+        let columns = self.width.saturating_sub(x0).div_ceil(dx);
+        let rows = self.height.saturating_sub(y0).div_ceil(dy);
+        let stride = (columns * self.bits_per_pixel()).div_ceil(8);
+        (columns, rows, stride)
+    }
+
+    /// The size of the inflated image data, every scanline starts with a filter type byte
+    fn raw_size(&self) -> Option<usize> {
+        self.passes().iter().try_fold(0usize, |size, &pass| {
+            let (columns, rows, stride) = self.pass_size(pass);
+            if columns == 0 || rows == 0 {
+                return Some(size);
+            }
+            rows.checked_mul(stride.checked_add(1)?)?.checked_add(size)
+        })
+    }
+}
+
+/// Represents a PNG image stored in a lump, as used by ZDoom-era PWADs for sprites,
+/// patches and flats.
+///
+/// Supports every standard color type and bit depth, interlaced images and transparency
+/// from alpha channels or the tRNS chunk. The private grAb chunk holds the left and top
+/// offsets which Doom patches store in their header:
+/// - 4 bytes: Left offset (i32, big-endian)
+/// - 4 bytes: Top offset (i32, big-endian)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PngImage {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+    palette: Option<Vec<[u8; 3]>>,
+    palette_indices: Option<Vec<u8>>,
+    offsets: Option<(i32, i32)>,
+}
+
+impl PngImage {
+    /// Decodes a PNG image.
+    ///
+    /// # Arguments
+    /// - `data`: The complete PNG file.
+    /// # Returns
+    /// - `Ok(PngImage)` with the decoded pixels.
+    /// - `Err` if the data is no valid PNG file, a chunk is corrupt or the image uses
+    ///   an unsupported feature.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if !is_png(data) {
+            return Err("Missing PNG signature".into());
+        }
+
+        let mut header = None;
+        let mut palette: Option<Vec<[u8; 3]>> = None;
+        let mut transparency: Option<&[u8]> = None;
+        let mut offsets = None;
+        let mut compressed = Vec::new();
+
+        let mut pos = PNG_SIGNATURE.len();
+        loop {
+            let length = read_u32(data, pos)? as usize;
+            let chunk_end = pos + 8 + length;
+            let chunk_type = data
+                .get(pos + 4..pos + 8)
+                .ok_or("Unexpected end of PNG data")?;
+            let chunk = data
+                .get(pos + 8..chunk_end)
+                .ok_or("PNG chunk out of range")?;
+            if crc32fast::hash(&data[pos + 4..chunk_end]) != read_u32(data, chunk_end)? {
+                return Err("PNG chunk is corrupt".into());
+            }
+            pos = chunk_end + 4;
+
+            match chunk_type {
+                b"IHDR" => header = Some(ImageHeader::from_chunk(chunk)?),
+                b"PLTE" => {
+                    palette = Some(chunk.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect());
+                }
+                b"tRNS" => transparency = Some(chunk),
+                b"grAb" => {
+                    if chunk.len() == 8 {
+                        offsets = Some((read_u32(chunk, 0)? as i32, read_u32(chunk, 4)? as i32));
+                    }
+                }
+                b"IDAT" => compressed.extend_from_slice(chunk),
+                b"IEND" => break,
+                // unknown ancillary chunks (lower case first letter) can be skipped safely
+                _ if chunk_type[0].is_ascii_lowercase() => {}
+                _ => {
+                    return Err(format!(
+                        "Unsupported PNG chunk '{}'",
+                        String::from_utf8_lossy(chunk_type)
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let header = header.ok_or("PNG image has no IHDR chunk")?;
+        if header.color_type == COLOR_PALETTE && palette.is_none() {
+            return Err("Paletted PNG image has no PLTE chunk".into());
+        }
+
+        // the pixel buffers are only allocated once the image data has the size of the header,
+        // so neither a forged header nor a zip bomb can make the decoder allocate more memory
+        header
+            .width
+            .checked_mul(header.height)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or("PNG image too large")?;
+        let raw_size = header.raw_size().ok_or("PNG image too large")?;
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&compressed, raw_size)
+            .map_err(|e| format!("Failed to inflate PNG image data: {}", e))?;
+        if raw.len() != raw_size {
+            return Err(format!(
+                "PNG image data has {} bytes, expected {}",
+                raw.len(),
+                raw_size
+            )
+            .into());
+        }
+        let mut decoder = PixelDecoder::new(header, palette.as_deref(), transparency);
+        decoder.decode(&raw)?;
+
+        Ok(Self {
+            width: header.width as u32,
+            height: header.height as u32,
+            rgba: decoder.rgba,
+            palette_indices: (header.color_type == COLOR_PALETTE).then_some(decoder.indices),
+            palette,
+            offsets,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The offsets of the grAb chunk, `None` if the image has none
+    pub fn offsets(&self) -> Option<(i32, i32)> {
+        self.offsets
+    }
+
+    pub fn left_offset(&self) -> i32 {
+        self.offsets.map_or(0, |(left, _)| left)
+    }
+
+    pub fn top_offset(&self) -> i32 {
+        self.offsets.map_or(0, |(_, top)| top)
+    }
+
+    /// The decoded pixels, `width * height * 4` bytes row by row
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// The palette of a paletted PNG
+    pub fn palette(&self) -> Option<&[[u8; 3]]> {
+        self.palette.as_deref()
+    }
+
+    /// The indices into the PNG palette of a paletted PNG, row by row
+    pub fn palette_indices(&self) -> Option<&[u8]> {
+        self.palette_indices.as_deref()
+    }
+
+    /// Maps the image to the indices of a Doom palette.
+    ///
+    /// The colors of a paletted PNG are mapped once per palette entry, entries with the same
+    /// color as the Doom palette entry at the same index keep their index, so a PNG saved with
+    /// the Doom palette keeps its exact indices even where the palette has duplicate colors.
+    /// Other images are mapped per pixel to the nearest color. Pixels with an alpha value
+    /// below 128 are transparent.
+    pub fn indexed_image(&self, palette: &Palette) -> IndexedImage {
+        let mut image = IndexedImage::new(self.width as usize, self.height as usize);
+        let remap: Option<Vec<u8>> = self.palette.as_ref().map(|colors| {
+            colors
+                .iter()
+                .enumerate()
+                .map(|(index, &rgb)| match palette.get_rgb(index) {
+                    Some(&color) if color == rgb => index as u8,
+                    _ => palette.nearest_index(rgb),
+                })
+                .collect()
+        });
+        let mut nearest: HashMap<[u8; 3], u8> = HashMap::new();
+
+        for (pos, rgba) in self.rgba.chunks_exact(4).enumerate() {
+            if rgba[3] < ALPHA_THRESHOLD {
+                continue;
+            }
+
+            let index = match (&remap, &self.palette_indices) {
+                (Some(remap), Some(indices)) => remap[indices[pos] as usize],
+                _ => {
+                    let rgb = [rgba[0], rgba[1], rgba[2]];
+                    *nearest
+                        .entry(rgb)
+                        .or_insert_with(|| palette.nearest_index(rgb))
+                }
+            };
+            image.set(pos % self.width as usize, pos / self.width as usize, index);
+        }
+
+        image
+    }
+}
+
+impl TryFrom<&[u8]> for PngImage {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        PngImage::from_bytes(value)
+    }
+}
+
+/// Unfilters the scanlines and converts the samples to RGBA and palette indices
+struct PixelDecoder<'a> {
+    header: ImageHeader,
+    palette: Option<&'a [[u8; 3]]>,
+    transparency: Option<&'a [u8]>,
+    rgba: Vec<u8>,
+    indices: Vec<u8>,
+}
+
+impl<'a> PixelDecoder<'a> {
+    fn new(
+        header: ImageHeader,
+        palette: Option<&'a [[u8; 3]]>,
+        transparency: Option<&'a [u8]>,
+    ) -> Self {
+        let pixels = header.width * header.height;
+        Self {
+            header,
+            palette,
+            transparency,
+            rgba: vec![0; pixels * 4],
+            indices: vec![0; pixels],
+        }
+    }
+
+    fn decode(&mut self, raw: &[u8]) -> Result<()> {
+        let mut pos = 0;
+        for &(x0, y0, dx, dy) in self.header.passes() {
+            let (columns, rows, stride) = self.header.pass_size((x0, y0, dx, dy));
+            if columns == 0 || rows == 0 {
+                continue;
+            }
+
+            let bytes_per_pixel = self.header.bits_per_pixel().div_ceil(8);
+            let mut previous = vec![0u8; stride];
+            for row in 0..rows {
+                let filter = *raw.get(pos).ok_or("PNG image data too short")?;
+                let mut line = raw
+                    .get(pos + 1..pos + 1 + stride)
+                    .ok_or("PNG image data too short")?
+                    .to_vec();
+                pos += 1 + stride;
+
+                unfilter(filter, &mut line, &previous, bytes_per_pixel)?;
+                for column in 0..columns {
+                    let pixel = (y0 + row * dy) * self.header.width + x0 + column * dx;
+                    self.store_pixel(&line, column, pixel)?;
+                }
+                previous = line;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads sample `channel` of the pixel at `column`, returns the raw and the 8 bit value
+    fn sample(&self, line: &[u8], column: usize, channel: usize) -> (u16, u8) {
+        let depth = self.header.bit_depth as usize;
+        let index = column * self.header.channels() + channel;
+        match depth {
+            16 => {
+                let raw = u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]);
+                (raw, (raw >> 8) as u8)
+            }
+            8 => (line[index] as u16, line[index]),
+            _ => {
+                let bit = index * depth;
+                let max = (1u16 << depth) - 1;
+                let raw = (line[bit / 8] as u16 >> (8 - depth - bit % 8)) & max;
+                (raw, (raw * 255 / max) as u8)
+            }
+        }
+    }
+
+    fn store_pixel(&mut self, line: &[u8], column: usize, pixel: usize) -> Result<()> {
+        let trns_value = |at: usize| {
+            self.transparency
+                .and_then(|trns| trns.get(at..at + 2))
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+
+        let rgba = match self.header.color_type {
+            COLOR_GRAY => {
+                let (raw, gray) = self.sample(line, column, 0);
+                let alpha = if trns_value(0) == Some(raw) { 0 } else { 255 };
+                [gray, gray, gray, alpha]
+            }
+            COLOR_RGB => {
+                let (r_raw, r) = self.sample(line, column, 0);
+                let (g_raw, g) = self.sample(line, column, 1);
+                let (b_raw, b) = self.sample(line, column, 2);
+                let transparent = trns_value(0) == Some(r_raw)
+                    && trns_value(2) == Some(g_raw)
+                    && trns_value(4) == Some(b_raw);
+                [r, g, b, if transparent { 0 } else { 255 }]
+            }
+            COLOR_PALETTE => {
+                let (index, _) = self.sample(line, column, 0);
+                let rgb = self
+                    .palette
+                    .and_then(|palette| palette.get(index as usize))
+                    .ok_or("PNG palette index out of range")?;
+                let alpha = self
+                    .transparency
+                    .and_then(|trns| trns.get(index as usize).copied())
+                    .unwrap_or(255);
+                self.indices[pixel] = index as u8;
+                [rgb[0], rgb[1], rgb[2], alpha]
+            }
+            COLOR_GRAY_ALPHA => {
+                let (_, gray) = self.sample(line, column, 0);
+                let (_, alpha) = self.sample(line, column, 1);
+                [gray, gray, gray, alpha]
+            }
+            _ => [0, 1, 2, 3].map(|channel| self.sample(line, column, channel).1),
+        };
+
+        self.rgba[pixel * 4..pixel * 4 + 4].copy_from_slice(&rgba);
+        Ok(())
+    }
+}
+
+/// Reverses the PNG scanline filter in place
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], bytes_per_pixel: usize) -> Result<()> {
+    for i in 0..line.len() {
+        let left = if i >= bytes_per_pixel {
+            line[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up = previous[i];
+        let up_left = if i >= bytes_per_pixel {
+            previous[i - bytes_per_pixel]
+        } else {
+            0
+        };
+
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("Invalid PNG filter type {}", filter).into()),
+        };
+        line[i] = line[i].wrapping_add(predictor);
+    }
+
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+        png.extend(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend(chunk_type);
+        png.extend(data);
+        let crc = crc32fast::hash(&png[start..]);
+        png.extend(&crc.to_be_bytes());
+    }
+
+    /// Builds a PNG file from already filtered scanlines
+    fn png(
+        width: u32,
+        height: u32,
+        depth_and_color: [u8; 2],
+        interlaced: bool,
+        extra: &[(&[u8; 4], Vec<u8>)],
+        scanlines: &[u8],
+    ) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        let mut ihdr = Vec::new();
+        ihdr.extend(&width.to_be_bytes());
+        ihdr.extend(&height.to_be_bytes());
+        ihdr.extend(&[
+            depth_and_color[0],
+            depth_and_color[1],
+            0,
+            0,
+            interlaced as u8,
+        ]);
+        chunk(&mut data, b"IHDR", &ihdr);
+        for (chunk_type, content) in extra {
+            chunk(&mut data, chunk_type, content);
+        }
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(scanlines, 6);
+        chunk(&mut data, b"IDAT", &compressed);
+        chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    /// Applies a filter to an unfiltered line, the inverse of `unfilter`
+    fn filter(filter: u8, line: &[u8], previous: &[u8], bytes_per_pixel: usize) -> Vec<u8> {
+        let mut filtered = vec![filter];
+        for i in 0..line.len() {
+            let left = if i >= bytes_per_pixel {
+                line[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let predictor = match filter {
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => 0,
+            };
+            filtered.push(line[i].wrapping_sub(predictor));
+        }
+        filtered
+    }
+
+    #[test]
+    fn png_rejects_invalid_data() {
+        assert!(PngImage::from_bytes(&[0u8; 16]).is_err());
+        let mut data = png(1, 1, [8, COLOR_GRAY], false, &[], &[0, 7]);
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(PngImage::from_bytes(&data).is_err());
+    }
+
+    #[test]
+    fn png_rejects_image_data_of_the_wrong_size() {
+        // 2x2 gray image needs 2 * (1 + 2) bytes
+        let valid = png(2, 2, [8, COLOR_GRAY], false, &[], &[0, 1, 2, 0, 3, 4]);
+        assert!(PngImage::from_bytes(&valid).is_ok());
+        let short = png(2, 2, [8, COLOR_GRAY], false, &[], &[0, 1, 2, 0, 3]);
+        assert!(PngImage::from_bytes(&short).is_err());
+        let long = png(2, 2, [8, COLOR_GRAY], false, &[], &[0, 1, 2, 0, 3, 4, 0]);
+        assert!(PngImage::from_bytes(&long).is_err());
+
+        // a tiny image whose data inflates to megabytes is rejected
+        let bomb = png(1, 1, [8, COLOR_GRAY], false, &[], &vec![0u8; 1 << 24]);
+        assert!(bomb.len() < 1 << 16);
+        assert!(PngImage::from_bytes(&bomb).is_err());
+    }
+
+    #[test]
+    fn png_decodes_rgba_with_all_filters_and_grab_offsets() {
+        let rows: Vec<Vec<u8>> = (0..5u8)
+            .map(|y| (0..8u8).map(|i| y * 40 + i * 5).collect())
+            .collect();
+        let mut scanlines = Vec::new();
+        let mut previous = vec![0u8; 8];
+        for (filter_type, row) in rows.iter().enumerate() {
+            scanlines.extend(filter(filter_type as u8, row, &previous, 4));
+            previous = row.clone();
+        }
+        let mut grab = (-12i32).to_be_bytes().to_vec();
+        grab.extend(&34i32.to_be_bytes());
+
+        let data = png(2, 5, [8, COLOR_RGBA], false, &[(b"grAb", grab)], &scanlines);
+        let image = PngImage::from_bytes(&data).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 5));
+        assert_eq!(image.offsets(), Some((-12, 34)));
+        assert_eq!(image.rgba(), rows.concat().as_slice());
+        assert!(image.palette_indices().is_none());
+    }
+
+    #[test]
+    fn png_decodes_low_bit_depth_palette_with_transparency() {
+        let palette = vec![0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+        // 4x1 pixels with 2 bits each: indices 0, 1, 2, 3
+        let data = png(
+            4,
+            1,
+            [2, COLOR_PALETTE],
+            false,
+            &[(b"PLTE", palette.clone()), (b"tRNS", vec![0])],
+            &[0, 0b00_01_10_11],
+        );
+        let image = PngImage::from_bytes(&data).unwrap();
+        assert_eq!(image.palette_indices(), Some(&[0, 1, 2, 3][..]));
+        assert_eq!(&image.rgba()[0..8], &[0, 0, 0, 0, 255, 0, 0, 255]);
+        assert_eq!(image.offsets(), None);
+
+        // map to a Doom palette where the colors sit at other indices
+        let mut doom_palette = vec![0u8; 768];
+        doom_palette[30..33].copy_from_slice(&[250, 0, 0]);
+        doom_palette[60..63].copy_from_slice(&[0, 0, 250]);
+        let doom_palette = Palette::from_bytes(&doom_palette).unwrap();
+        let indexed = image.indexed_image(&doom_palette);
+        assert_eq!(indexed.get(0, 0), None);
+        assert_eq!(indexed.get(1, 0), Some(10));
+        assert_eq!(indexed.get(3, 0), Some(20));
+    }
+
+    #[test]
+    fn png_with_doom_palette_keeps_duplicate_color_indices() {
+        // like the Doom palette, indices 0 and 2 share a color
+        let mut colors = [[0u8; 3]; 256];
+        colors[1] = [200, 0, 0];
+        colors[3] = [0, 200, 0];
+        let palette = Palette::from_colors(colors);
+
+        let plte: Vec<u8> = colors[..4].concat();
+        // 4x1 pixels with indices 2, 0, 1, 3
+        let data = png(
+            4,
+            1,
+            [8, COLOR_PALETTE],
+            false,
+            &[(b"PLTE", plte.clone())],
+            &[0, 2, 0, 1, 3],
+        );
+        let indexed = PngImage::from_bytes(&data).unwrap().indexed_image(&palette);
+        let indices: Vec<_> = (0..4).map(|x| indexed.get(x, 0)).collect();
+        assert_eq!(indices, [Some(2), Some(0), Some(1), Some(3)]);
+
+        // a color which differs from the Doom palette entry at its index is remapped
+        let mut plte = plte;
+        plte[6..9].copy_from_slice(&[0, 190, 0]);
+        let data = png(1, 1, [8, COLOR_PALETTE], false, &[(b"PLTE", plte)], &[0, 2]);
+        let indexed = PngImage::from_bytes(&data).unwrap().indexed_image(&palette);
+        assert_eq!(indexed.get(0, 0), Some(3));
+    }
+
+    #[test]
+    fn png_decodes_interlaced_images() {
+        // 3x3 gray image with pixel value y * 3 + x, split into the Adam7 passes
+        let value = |x: usize, y: usize| (y * 3 + x) as u8 * 10;
+        let mut scanlines = Vec::new();
+        for (x0, y0, dx, dy) in ADAM7_PASSES {
+            for y in (y0..3).step_by(dy) {
+                let line: Vec<u8> = (x0..3).step_by(dx).map(|x| value(x, y)).collect();
+                if !line.is_empty() {
+                    scanlines.push(0);
+                    scanlines.extend(line);
+                }
+            }
+        }
+
+        let data = png(3, 3, [8, COLOR_GRAY], true, &[], &scanlines);
+        let image = PngImage::from_bytes(&data).unwrap();
+        let gray: Vec<u8> = image.rgba().chunks(4).map(|rgba| rgba[0]).collect();
+        assert_eq!(gray, (0..9).map(|i| i * 10).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::graphics::Palette;
+use crate::png::is_png;
+use crate::rgba::RgbaTable;
+use crate::sprite::Sprite;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Width of a raw fullscreen picture
+pub const RAW_PICTURE_WIDTH: u16 = 320;
+/// Height of a raw fullscreen picture
+pub const RAW_PICTURE_HEIGHT: u16 = 200;
+/// Size of a raw fullscreen picture lump in bytes
+pub const RAW_PICTURE_SIZE: usize = RAW_PICTURE_WIDTH as usize * RAW_PICTURE_HEIGHT as usize;
+
+/// Fullscreen lumps which Heretic and Hexen store as raw pictures
+pub const RAW_PICTURE_LUMPS: &[&str] = &[
+    "TITLE", "HELP1", "HELP2", "CREDIT", "E2END", "FINAL1", "FINAL2", "FINALE1", "FINALE2",
+    "FINALE3", "INTERPIC",
+];
+
+/// Checks whether a lump is a raw fullscreen picture.
+///
+/// The lump must be exactly 64000 bytes and no PNG image. Lumps of `RAW_PICTURE_LUMPS`
+/// are always raw pictures, other lumps only if they are no valid patch.
+///
+/// # Arguments
+/// - `name`: The lump name.
+/// - `lump_data`: The lump data.
+pub fn is_raw_picture(name: &str, lump_data: &[u8]) -> bool {
+    if lump_data.len() != RAW_PICTURE_SIZE || is_png(lump_data) {
+        return false;
+    }
+
+    RAW_PICTURE_LUMPS.contains(&name.to_ascii_uppercase().as_str())
+        || !Sprite::new(lump_data).is_ok_and(|sprite| sprite.is_valid())
+}
+
+/// Represents a raw fullscreen picture of Heretic and Hexen.
+///
+/// # Structure of a Raw Picture Lump
+/// The lump has no header, it is a 320x200 block of palette indices row by row like
+/// the VGA screen buffer.
+#[derive(Debug, Clone)]
+pub struct RawPicture<'a> {
+    pixels: &'a [u8],
+}
+
+impl<'a> RawPicture<'a> {
+    /// Creates a `RawPicture` from the complete lump slice.
+    ///
+    /// # Arguments
+    /// - `lump_data`: The data of the picture lump.
+    /// # Returns
+    /// - `Ok(RawPicture)` if the lump has the size of a 320x200 screen.
+    /// - `Err` if the lump has any other size.
+    pub fn new(lump_data: &'a [u8]) -> Result<Self> {
+        if lump_data.len() != RAW_PICTURE_SIZE {
+            return Err("Raw picture lump must be 64000 bytes".into());
+        }
+
+        Ok(Self { pixels: lump_data })
+    }
+
+    pub fn width(&self) -> u16 {
+        RAW_PICTURE_WIDTH
+    }
+
+    pub fn height(&self) -> u16 {
+        RAW_PICTURE_HEIGHT
+    }
+
+    /// The palette indices of the picture, row by row
+    pub fn indexed_pixels(&self) -> &'a [u8] {
+        self.pixels
+    }
+
+    /// Converts the picture into an RGBA pixel buffer with `320 * 200 * 4` bytes.
+    /// Raw pictures are always opaque.
+    pub fn rgba_pixel_buffer(&self, palette: &Palette) -> Result<Vec<u8>> {
+        Ok(RgbaTable::new(palette).convert(self.pixels))
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for RawPicture<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        RawPicture::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_picture_requires_screen_size() {
+        assert!(RawPicture::new(&[0u8; 64001]).is_err());
+        assert!(RawPicture::new(&[0u8; 4096]).is_err());
+
+        let data = vec![7u8; RAW_PICTURE_SIZE];
+        let palette_data: Vec<u8> = (0..768).map(|val: u16| (val % 256) as u8).collect();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let rgba = RawPicture::new(&data)
+            .unwrap()
+            .rgba_pixel_buffer(&palette)
+            .unwrap();
+        assert_eq!(rgba.len(), 320 * 200 * 4);
+        assert_eq!(&rgba[0..4], &[21, 22, 23, 255]);
+    }
+
+    #[test]
+    fn raw_picture_detection_uses_size_and_name() {
+        // a 64000 byte lump that happens to parse as a 1x1 patch
+        let mut data = vec![0u8; RAW_PICTURE_SIZE];
+        data[..18].copy_from_slice(&[1, 0, 1, 0, 0, 0, 0, 0, 12, 0, 0, 0, 0, 1, 0, 5, 0, 0xFF]);
+
+        assert!(is_raw_picture("TITLE", &data));
+        assert!(is_raw_picture("finale1", &data));
+        assert!(!is_raw_picture("M_DOOM", &data));
+        assert!(is_raw_picture("M_DOOM", &[0xAB; RAW_PICTURE_SIZE]));
+        assert!(!is_raw_picture("TITLE", &data[..4000]));
+
+        let mut png = crate::png::PNG_SIGNATURE.to_vec();
+        png.resize(RAW_PICTURE_SIZE, 0);
+        assert!(!is_raw_picture("TITLE", &png));
+    }
+}
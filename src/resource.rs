@@ -0,0 +1,258 @@
+use crate::index::{LumpNode, LumpSource, merge_lump_index};
+use crate::lump::{LUMP_NAME_LENGTH, LumpRef};
+use crate::wad::WadIndex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Top level folders of path based containers (PK3 archives, loose directories) and
+/// the WAD namespace marker they replace. Folders without a marker hold lumps which
+/// live at the top level of a WAD.
+pub const NAMESPACE_FOLDERS: &[(&str, Option<&str>)] = &[
+    ("sprites", Some("S_START")),
+    ("flats", Some("F_START")),
+    ("patches", Some("P_START")),
+    ("textures", Some("TX_START")),
+    ("hires", Some("HI_START")),
+    ("colormaps", Some("C_START")),
+    ("acs", Some("A_START")),
+    ("voices", Some("V_START")),
+    ("voxels", Some("VX_START")),
+    ("sounds", None),
+    ("music", None),
+    ("graphics", None),
+];
+
+/// Converts a file path into a lump name using the usual 8 character rules:
+/// the folder and everything after the first dot is dropped, the name is upper cased,
+/// '^' is replaced by '\' (which is not allowed in file names) and the result is
+/// truncated to 8 characters.
+///
+/// # Returns
+/// - `Some(String)` with the lump name.
+/// - `None` if the file name does not produce a valid lump name.
+pub fn lump_name_from_path(path: &str) -> Option<String> {
+    let file_name = path.rsplit(['/', '\\']).next()?;
+    let stem = file_name.split('.').next()?;
+    if stem.is_empty() || !stem.is_ascii() {
+        return None;
+    }
+
+    Some(
+        stem.chars()
+            .map(|c| {
+                if c == '^' {
+                    '\\'
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            })
+            .take(LUMP_NAME_LENGTH)
+            .collect(),
+    )
+}
+
+/// A file of a path based container with its derived lump name.
+/// Owns the (decompressed) file content, the index borrows from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceFile {
+    path: String,
+    name: String,
+    data: Vec<u8>,
+}
+
+impl ResourceFile {
+    pub fn new(path: String, data: Vec<u8>) -> Result<Self> {
+        let name = lump_name_from_path(&path)
+            .ok_or_else(|| format!("Cannot derive a lump name from '{}'", path))?;
+        Ok(Self { path, name, data })
+    }
+
+    /// Path of the file inside the container, always separated by '/'
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Embedded WADs are indexed and merged into the tree of their container
+    pub fn is_wad(&self) -> bool {
+        self.path.to_ascii_lowercase().ends_with(".wad")
+    }
+}
+
+/// Where a file of a path based container ends up in the lump tree
+enum Placement {
+    TopLevel,
+    Namespace(&'static str),
+    Ignored,
+}
+
+fn placement(path: &str) -> Placement {
+    let Some((folder, _)) = path.split_once('/') else {
+        return Placement::TopLevel;
+    };
+
+    let folder = folder.to_ascii_lowercase();
+    match NAMESPACE_FOLDERS.iter().find(|(name, _)| *name == folder) {
+        Some((_, Some(marker))) => Placement::Namespace(marker),
+        Some((_, None)) => Placement::TopLevel,
+        // files in unknown folders are not addressable by lump name
+        None => Placement::Ignored,
+    }
+}
+
+/// Lump tree of a path based container, with the same namespace structure as `WadIndex`.
+///
+/// Files in the folders of `NAMESPACE_FOLDERS` are placed into the matching namespace
+/// (sub folders are flattened), files in the root or in folders without a namespace go
+/// to the top level, and embedded WADs (e.g. `maps/MAP01.wad`) are indexed and merged
+/// into the tree so their maps appear in the `MAPS` namespace.
+pub struct ResourceIndex<'a> {
+    name: String,
+    lump_index: HashMap<Cow<'a, str>, LumpNode<'a>>,
+}
+
+impl<'a> ResourceIndex<'a> {
+    pub fn from_files(name: String, files: &'a [ResourceFile]) -> Result<Self> {
+        let mut lump_index: HashMap<Cow<'a, str>, LumpNode<'a>> = HashMap::new();
+        lump_index.insert("MAPS".into(), LumpNode::namespace("MAPS", HashMap::new()));
+
+        for file in files {
+            if file.is_wad() {
+                let wad = WadIndex::from_bytes(file.path.clone(), &file.data)
+                    .map_err(|e| format!("Failed to index embedded WAD '{}': {}", file.path, e))?;
+                merge_lump_index(&mut lump_index, wad.into_lump_index());
+                continue;
+            }
+
+            let target = match placement(&file.path) {
+                Placement::TopLevel => &mut lump_index,
+                Placement::Namespace(marker) => {
+                    let node = lump_index
+                        .entry(marker.into())
+                        .or_insert_with(|| LumpNode::namespace(marker, HashMap::new()));
+                    match node {
+                        LumpNode::Namespace { children, .. } => children,
+                        LumpNode::Lump { .. } => {
+                            return Err(
+                                format!("Lump '{}' clashes with a namespace", marker).into()
+                            );
+                        }
+                    }
+                }
+                Placement::Ignored => continue,
+            };
+
+            let name = file.name.as_str();
+            target.insert(
+                name.into(),
+                LumpNode::lump(name, LumpRef::new(&file.data, name)),
+            );
+        }
+
+        Ok(Self { name, lump_index })
+    }
+
+    pub fn get_lump_index(&self) -> &HashMap<Cow<'a, str>, LumpNode<'a>> {
+        &self.lump_index
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<'a> LumpSource<'a> for ResourceIndex<'a> {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_lump_index(&self) -> &HashMap<Cow<'a, str>, LumpNode<'a>> {
+        &self.lump_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PWAD containing a single map marker followed by a THINGS lump
+    fn map_wad(map: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(b"PWAD");
+        data.extend(&2i32.to_le_bytes());
+        data.extend(&16i32.to_le_bytes());
+        data.extend(&[1, 2, 3, 4]); // THINGS data
+        let mut marker = [0u8; 8];
+        marker[..map.len()].copy_from_slice(map);
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&marker);
+        data.extend(&12i32.to_le_bytes());
+        data.extend(&4i32.to_le_bytes());
+        data.extend(b"THINGS\0\0");
+        data
+    }
+
+    #[test]
+    fn lump_name_from_path_applies_8_character_rules() {
+        assert_eq!(lump_name_from_path("sprites/trooa1.png").unwrap(), "TROOA1");
+        assert_eq!(
+            lump_name_from_path("music/d_runnin.mid").unwrap(),
+            "D_RUNNIN"
+        );
+        assert_eq!(
+            lump_name_from_path("sprites/vilea^d.lmp").unwrap(),
+            "VILEA\\D"
+        );
+        assert_eq!(lump_name_from_path("mapinfo.txt.bak").unwrap(), "MAPINFO");
+        assert_eq!(
+            lump_name_from_path("textures/verylongname.png").unwrap(),
+            "VERYLONG"
+        );
+        assert!(lump_name_from_path("sprites/.hidden").is_none());
+    }
+
+    #[test]
+    fn resource_index_maps_folders_to_namespaces() {
+        let files = vec![
+            ResourceFile::new("sprites/monsters/trooa1.lmp".to_string(), vec![1]).unwrap(),
+            ResourceFile::new("flats/floor0_1.lmp".to_string(), vec![2]).unwrap(),
+            ResourceFile::new("sounds/dspistol.lmp".to_string(), vec![3]).unwrap(),
+            ResourceFile::new("decorate.txt".to_string(), vec![4]).unwrap(),
+            ResourceFile::new("filter/doom.id/readme.txt".to_string(), vec![5]).unwrap(),
+        ];
+        let index = ResourceIndex::from_files("test.pk3".to_string(), &files).unwrap();
+
+        assert!(index.get_lump(vec!["S_START"], "TROOA1").is_some());
+        assert!(index.get_lump(vec!["F_START"], "FLOOR0_1").is_some());
+        assert!(index.get_lump(vec![], "DSPISTOL").is_some());
+        assert!(index.get_lump(vec![], "DECORATE").is_some());
+        assert!(index.get_lump(vec![], "README").is_none());
+    }
+
+    #[test]
+    fn resource_index_merges_embedded_wads_into_maps() {
+        let files = vec![
+            ResourceFile::new("maps/map01.wad".to_string(), map_wad(b"MAP01")).unwrap(),
+            ResourceFile::new("maps/map02.wad".to_string(), map_wad(b"MAP02")).unwrap(),
+        ];
+        let index = ResourceIndex::from_files("test.pk3".to_string(), &files).unwrap();
+
+        let maps = index.get_maps().unwrap();
+        assert_eq!(maps.len(), 2);
+        match index.get_lump(vec!["MAPS", "MAP02"], "THINGS").unwrap() {
+            LumpNode::Lump { lump, .. } => assert_eq!(lump.data(), &[1, 2, 3, 4]),
+            _ => panic!("THINGS is not a lump"),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::graphics::Palette;
+use wide::u32x8;
+
+/// Number of pixels converted per SIMD step
+const LANES: usize = 8;
+
+/// A palette packed into one 32 bit RGBA value per index, for bulk conversions of palette
+/// indices into RGBA pixel buffers.
+///
+/// Every `u8` is a valid index, so the conversions need no bounds check or error handling
+/// per pixel. The pixels are converted eight at a time with `wide` vectors, transparent
+/// pixels are cleared with a lane mask instead of a branch per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaTable {
+    colors: [u32; 256],
+}
+
+impl RgbaTable {
+    /// Packs the colors of the palette with an alpha value of 255
+    pub fn new(palette: &Palette) -> Self {
+        let mut colors = [0u32; 256];
+        for (index, color) in colors.iter_mut().enumerate() {
+            if let Some(rgba) = palette.get_rgba(index) {
+                *color = u32::from_le_bytes(rgba);
+            }
+        }
+
+        Self { colors }
+    }
+
+    /// Returns the RGBA value of a palette index
+    pub fn get(&self, index: u8) -> [u8; 4] {
+        self.colors[index as usize].to_le_bytes()
+    }
+
+    /// Converts palette indices into an RGBA pixel buffer with 4 bytes per index
+    pub fn convert(&self, indices: &[u8]) -> Vec<u8> {
+        let mut pixel_buffer = vec![0u8; indices.len() * 4];
+        let mut chunks = indices.chunks_exact(LANES);
+        let mut out = pixel_buffer.chunks_exact_mut(LANES * 4);
+        for (chunk, rgba) in (&mut chunks).zip(&mut out) {
+            write_lanes(rgba, self.gather(chunk));
+        }
+        self.convert_remainder(chunks.remainder(), None, out.into_remainder());
+
+        pixel_buffer
+    }
+
+    /// Converts palette indices with a transparency mask into an RGBA pixel buffer,
+    /// pixels whose mask is `false` become (0, 0, 0, 0).
+    ///
+    /// # Arguments
+    /// - `indices`: The palette indices.
+    /// - `mask`: `true` for every opaque pixel, as long as `indices`.
+    /// # Returns
+    /// - `Vec<u8>` with 4 bytes per index. If the mask is shorter than the indices the
+    ///   pixels without a mask value are transparent.
+    pub fn convert_masked(&self, indices: &[u8], mask: &[bool]) -> Vec<u8> {
+        let mut pixel_buffer = vec![0u8; indices.len() * 4];
+        let len = indices.len().min(mask.len());
+        let mut chunks = indices[..len].chunks_exact(LANES);
+        let mut masks = mask[..len].chunks_exact(LANES);
+        let mut out = pixel_buffer[..len * 4].chunks_exact_mut(LANES * 4);
+        for ((chunk, opaque), rgba) in (&mut chunks).zip(&mut masks).zip(&mut out) {
+            write_lanes(rgba, self.gather(chunk) & lane_mask(opaque));
+        }
+        self.convert_remainder(
+            chunks.remainder(),
+            Some(masks.remainder()),
+            out.into_remainder(),
+        );
+
+        pixel_buffer
+    }
+
+    fn gather(&self, chunk: &[u8]) -> u32x8 {
+        u32x8::new(std::array::from_fn(|lane| {
+            self.colors[chunk[lane] as usize]
+        }))
+    }
+
+    fn convert_remainder(&self, indices: &[u8], mask: Option<&[bool]>, out: &mut [u8]) {
+        for (pixel, (&index, rgba)) in indices.iter().zip(out.chunks_exact_mut(4)).enumerate() {
+            if mask.is_none_or(|mask| mask[pixel]) {
+                rgba.copy_from_slice(&self.get(index));
+            }
+        }
+    }
+}
+
+impl From<&Palette<'_>> for RgbaTable {
+    fn from(palette: &Palette) -> Self {
+        RgbaTable::new(palette)
+    }
+}
+
+/// All bits set for opaque lanes, none for transparent ones
+fn lane_mask(opaque: &[bool]) -> u32x8 {
+    let bits = u32x8::new(std::array::from_fn(|lane| opaque[lane] as u32));
+    // 0 - 1 wraps around to all bits set
+    u32x8::splat(0) - bits
+}
+
+fn write_lanes(out: &mut [u8], colors: u32x8) {
+    for (rgba, color) in out.chunks_exact_mut(4).zip(colors.to_array()) {
+        rgba.copy_from_slice(&color.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Palette<'static> {
+        let mut colors = [[0u8; 3]; 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = [i as u8, 255 - i as u8, (i as u8).wrapping_mul(3)];
+        }
+        Palette::from_colors(colors)
+    }
+
+    fn scalar(indices: &[u8], mask: Option<&[bool]>, palette: &Palette) -> Vec<u8> {
+        let mut pixel_buffer = vec![0u8; indices.len() * 4];
+        for (i, (&index, rgba)) in indices
+            .iter()
+            .zip(pixel_buffer.chunks_exact_mut(4))
+            .enumerate()
+        {
+            if mask.is_none_or(|mask| mask[i]) {
+                rgba.copy_from_slice(&palette.get_rgba(index as usize).unwrap());
+            }
+        }
+        pixel_buffer
+    }
+
+    #[test]
+    fn bulk_conversion_matches_palette_lookups() {
+        let palette = palette();
+        let table = RgbaTable::new(&palette);
+        assert_eq!(table.get(7), [7, 248, 21, 255]);
+
+        // not a multiple of the lane count, so the remainder path is used as well
+        let indices: Vec<u8> = (0..1003u32).map(|i| (i * 7 % 256) as u8).collect();
+        assert_eq!(table.convert(&indices), scalar(&indices, None, &palette));
+        assert!(table.convert(&[]).is_empty());
+    }
+
+    #[test]
+    fn masked_conversion_clears_transparent_pixels() {
+        let palette = palette();
+        let table = RgbaTable::from(&palette);
+        let indices: Vec<u8> = (0..37u32).map(|i| (i * 13 % 256) as u8).collect();
+        let mask: Vec<bool> = (0..37).map(|i| i % 3 != 0).collect();
+
+        let rgba = table.convert_masked(&indices, &mask);
+        assert_eq!(rgba, scalar(&indices, Some(&mask), &palette));
+        assert_eq!(&rgba[0..4], &[0, 0, 0, 0]);
+
+        // pixels without a mask value stay transparent
+        let rgba = table.convert_masked(&indices, &mask[..10]);
+        assert_eq!(rgba.len(), 37 * 4);
+        assert!(rgba[40..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn lanes_match_scalar_conversion_for_every_length() {
+        let palette = palette();
+        let table = RgbaTable::new(&palette);
+        // no, one and two full lanes with every remainder length
+        for len in 0..=2 * LANES + 1 {
+            let indices: Vec<u8> = (0..len).map(|i| (i * 31 + 5) as u8).collect();
+            let mask: Vec<bool> = (0..len).map(|i| i % 4 != 1).collect();
+            assert_eq!(table.convert(&indices), scalar(&indices, None, &palette));
+            assert_eq!(
+                table.convert_masked(&indices, &mask),
+                scalar(&indices, Some(&mask), &palette)
+            );
+        }
+
+        // a fully transparent and a fully opaque lane
+        let indices = [200u8; LANES * 2];
+        let mut mask = [true; LANES * 2];
+        mask[..LANES].fill(false);
+        let rgba = table.convert_masked(&indices, &mask);
+        assert!(rgba[..LANES * 4].iter().all(|&byte| byte == 0));
+        assert_eq!(&rgba[LANES * 4..LANES * 4 + 4], &table.get(200));
+    }
+}
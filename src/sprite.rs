@@ -1,8 +1,17 @@
-use crate::graphics::Palette;
+use crate::graphics::{IndexedImage, Palette};
+use crate::png::is_png;
+use crate::rgba::RgbaTable;
+use std::collections::HashMap;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 const HEADER_SIZE: usize = 8;
+/// Highest row a post can start at, 0xFF marks the end of a column
+const MAX_TOP_DELTA: usize = 254;
+/// Longest post the encoder writes
+const MAX_POST_LENGTH: usize = 254;
+/// Pixels with a lower alpha value are transparent when encoding
+const ALPHA_THRESHOLD: u8 = 128;
 
 /// Header of a Doom-Patch-/Sprite-Lump
 #[derive(Debug, Clone, Copy)]
@@ -53,6 +62,9 @@ impl SpriteHeader {
 /// - N bytes: Pixel data (palette indices)
 /// - 1 byte: 0xFF (end of column marker)
 ///
+/// Patches taller than 254 pixels (DeePsea tall patches) can't address their lower rows
+/// with a single byte, so a top delta which is not larger than the top delta of the previous
+/// post in the column is relative to the previous post.
 #[derive(Debug, Clone)]
 pub struct Sprite<'a> {
     lump_data: &'a [u8],
@@ -70,6 +82,9 @@ impl<'a> Sprite<'a> {
     /// - `Ok(Sprite)` if the sprite lump is valid.
     /// - `Err` if the sprite lump is invalid or out of bounds.
     pub fn new(lump_data: &'a [u8]) -> Result<Self> {
+        if is_png(lump_data) {
+            return Err("lump is a PNG image, decode it with Picture or PngImage".into());
+        }
         let header = SpriteHeader::from_bytes(lump_data)?;
         Self::check_size(header.width as usize, lump_data)?;
 
@@ -113,79 +128,100 @@ impl<'a> Sprite<'a> {
             return Err("sprite has zero width or height".into());
         }
 
-        let lump = self.lump_data;
+        // write the posts straight into the buffer, decoding into an `IndexedImage` first
+        // and converting that in bulk is slower because of the extra pass
+        let table = RgbaTable::new(palette);
+        let mut pixel_buffer = vec![0u8; w * h * 4];
+        self.for_each_post(|column, row_start, pixels| {
+            for (dy, &index) in pixels.iter().enumerate() {
+                let pos = ((row_start + dy) * w + column) * 4;
+                pixel_buffer[pos..pos + 4].copy_from_slice(&table.get(index));
+            }
+            Ok(())
+        })?;
 
-        Self::check_size(w, lump)?;
+        Ok(pixel_buffer)
+    }
 
-        // init RGBA pixel buffer with transparent pixels
-        let mut pixel_buffer = vec![0u8; w * h * 4];
+    /// Checks the complete column and post structure of the lump.
+    /// Used to tell patches apart from other binary lumps.
+    pub fn is_valid(&self) -> bool {
+        self.width() > 0 && self.height() > 0 && self.for_each_post(|_, _, _| Ok(())).is_ok()
+    }
 
-        for row in 0..w {
-            let offset_index = HEADER_SIZE + row * 4;
-            let column_offset = u32::from_le_bytes([
-                lump[offset_index],
-                lump[offset_index + 1],
-                lump[offset_index + 2],
-                lump[offset_index + 3],
-            ]) as usize;
-
-            if column_offset >= lump.len() {
-                return Err("column offset out of range".into());
+    /// Decodes the sprite into palette indices with a transparency mask, without going
+    /// through RGBA. Rows without posts stay transparent.
+    pub fn indexed_image(&self) -> Result<IndexedImage> {
+        let mut image = IndexedImage::new(self.width() as usize, self.height() as usize);
+        self.for_each_post(|column, row_start, pixels| {
+            for (dy, &index) in pixels.iter().enumerate() {
+                image.set(column, row_start + dy, index);
             }
+            Ok(())
+        })?;
 
-            let mut cursor = column_offset;
-            loop {
-                let topdelta = lump
-                    .get(cursor)
-                    .copied()
-                    .ok_or("unexpected end of post header")?;
-                cursor += 1;
-
-                // 0xFF marks the end of the column
-                if topdelta == 0xFF {
-                    break;
-                }
+        Ok(image)
+    }
 
-                let length = lump
-                    .get(cursor)
-                    .copied()
-                    .ok_or("unexpected end of post length")? as usize;
-                cursor += 2;
+    /// Returns an iterator over the posts of a single column.
+    ///
+    /// # Arguments
+    /// - `column`: The column index, `0..width`.
+    /// # Returns
+    /// - `Ok(Posts)` which borrows the pixel data of each post from the lump.
+    /// - `Err` if the column is out of range or its offset points outside the lump.
+    pub fn column(&self, column: usize) -> Result<Posts<'a>> {
+        let w = self.width() as usize;
+        if column >= w {
+            return Err("column out of range".into());
+        }
 
-                let data_start = cursor;
-                let data_end = data_start
-                    .checked_add(length)
-                    .ok_or("post length overflow")?;
+        let lump = self.lump_data;
+        Self::check_size(w, lump)?;
 
-                if data_end >= lump.len() {
-                    return Err("post data out of range".into());
-                }
+        let offset_index = HEADER_SIZE + column * 4;
+        let column_offset = u32::from_le_bytes([
+            lump[offset_index],
+            lump[offset_index + 1],
+            lump[offset_index + 2],
+            lump[offset_index + 3],
+        ]) as usize;
 
-                if data_end + 1 > lump.len() {
-                    return Err("post trailing byte missing".into());
-                }
+        if column_offset >= lump.len() {
+            return Err("column offset out of range".into());
+        }
 
-                let row_start = topdelta as usize;
-                if row_start >= h || row_start + length > h {
-                    return Err("post writes beyond sprite height".into());
-                }
+        Ok(Posts {
+            lump,
+            height: self.height() as usize,
+            cursor: column_offset,
+            top: None,
+            done: false,
+        })
+    }
 
-                let pixel_data = &lump[data_start..data_end];
-                for (dy, &index) in pixel_data.iter().enumerate() {
-                    let y = row_start + dy;
-                    let buffer_pos = (y * w + row) * 4;
-                    pixel_buffer[buffer_pos..buffer_pos + 4].copy_from_slice(
-                        palette
-                            .get_rgba(index as usize)
-                            .ok_or("palette index out of bounds")?
-                            .as_ref(),
-                    );
-                }
-                cursor = data_end + 1;
+    /// Returns an iterator over all columns from left to right, see `Sprite::column`
+    pub fn columns(&self) -> impl Iterator<Item = Result<Posts<'a>>> + '_ {
+        (0..self.width() as usize).map(|column| self.column(column))
+    }
+
+    /// Walks all posts of all columns and calls `f` with the column, the first row
+    /// and the palette indices of each post. Every post is checked against the lump
+    /// and sprite bounds before `f` is called.
+    pub(crate) fn for_each_post<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, usize, &'a [u8]) -> Result<()>,
+    {
+        Self::check_size(self.width() as usize, self.lump_data)?;
+
+        for (column, posts) in self.columns().enumerate() {
+            for post in posts? {
+                let post = post?;
+                f(column, post.row, post.pixels)?;
             }
         }
 
-        Ok(pixel_buffer)
+        Ok(())
     }
 
     fn check_size(w: usize, lump: &[u8]) -> Result<()> {
@@ -198,6 +234,195 @@ impl<'a> Sprite<'a> {
     }
 }
 
+/// A run of opaque pixels in a sprite column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Post<'a> {
+    /// The top delta as stored in the lump, relative to the previous post in tall patches
+    pub top_delta: u8,
+    /// The first row of the post
+    pub row: usize,
+    /// The palette indices of the post from top to bottom
+    pub pixels: &'a [u8],
+}
+
+/// Iterator over the posts of a sprite column, returned by `Sprite::column`.
+///
+/// Every post is checked against the lump and sprite bounds, iteration stops after
+/// the first error.
+#[derive(Debug, Clone)]
+pub struct Posts<'a> {
+    lump: &'a [u8],
+    height: usize,
+    cursor: usize,
+    top: Option<usize>,
+    done: bool,
+}
+
+impl<'a> Posts<'a> {
+    fn next_post(&mut self) -> Result<Option<Post<'a>>> {
+        let lump = self.lump;
+        let top_delta = lump
+            .get(self.cursor)
+            .copied()
+            .ok_or("unexpected end of post header")?;
+
+        // 0xFF marks the end of the column
+        if top_delta == 0xFF {
+            return Ok(None);
+        }
+
+        let length = lump
+            .get(self.cursor + 1)
+            .copied()
+            .ok_or("unexpected end of post length")? as usize;
+
+        let data_start = self.cursor + 3;
+        let data_end = data_start + length;
+        if data_end >= lump.len() {
+            return Err("post data out of range".into());
+        }
+
+        let row = match self.top {
+            Some(top) if top_delta as usize <= top => top + top_delta as usize,
+            _ => top_delta as usize,
+        };
+        if row >= self.height || row + length > self.height {
+            return Err("post writes beyond sprite height".into());
+        }
+
+        self.top = Some(row);
+        // skip the trailing byte of the post
+        self.cursor = data_end + 1;
+
+        Ok(Some(Post {
+            top_delta,
+            row,
+            pixels: &lump[data_start..data_end],
+        }))
+    }
+}
+
+impl<'a> Iterator for Posts<'a> {
+    type Item = Result<Post<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let post = self.next_post().transpose();
+        if !matches!(post, Some(Ok(_))) {
+            self.done = true;
+        }
+        post
+    }
+}
+
+/// Encodes an RGBA image into a Doom patch lump.
+///
+/// Pixels with an alpha value of at least 128 are opaque and mapped to the nearest palette
+/// color, all others are transparent. Each run of opaque pixels in a column becomes a post,
+/// runs longer than 254 pixels are split into several posts. Rows below 254 are addressed
+/// with relative top deltas like in DeePsea tall patches, with empty posts as stepping
+/// stones where the distance to the previous post is too large.
+///
+/// # Arguments
+/// - `header`: Width, height and offsets of the patch.
+/// - `rgba`: The image, `width * height * 4` bytes row by row.
+/// - `palette`: The palette the patch is drawn with.
+/// # Returns
+/// - `Ok(Vec<u8>)` with the patch lump.
+/// - `Err` if the buffer size does not match.
+pub fn encode_patch(header: SpriteHeader, rgba: &[u8], palette: &Palette) -> Result<Vec<u8>> {
+    let w = header.width as usize;
+    let h = header.height as usize;
+    if rgba.len() != w * h * 4 {
+        return Err("RGBA buffer size does not match the patch size".into());
+    }
+
+    let mut nearest: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut index_at = |x: usize, y: usize| {
+        let pos = (y * w + x) * 4;
+        let pixel = &rgba[pos..pos + 4];
+        (pixel[3] >= ALPHA_THRESHOLD).then(|| {
+            let rgb = [pixel[0], pixel[1], pixel[2]];
+            *nearest
+                .entry(rgb)
+                .or_insert_with(|| palette.nearest_index(rgb))
+        })
+    };
+
+    let mut columns: Vec<u8> = Vec::new();
+    let mut column_offsets = Vec::with_capacity(w);
+    let columns_start = HEADER_SIZE + w * 4;
+    for x in 0..w {
+        column_offsets.push(columns_start + columns.len());
+
+        let mut y = 0;
+        let mut top = None;
+        while y < h {
+            if index_at(x, y).is_none() {
+                y += 1;
+                continue;
+            }
+            let mut post = Vec::new();
+            while y + post.len() < h && post.len() < MAX_POST_LENGTH {
+                match index_at(x, y + post.len()) {
+                    Some(index) => post.push(index),
+                    None => break,
+                }
+            }
+
+            // empty posts move the top of tall columns closer to the next post
+            let mut delta = top_delta(top, y);
+            while delta.is_none() {
+                let step = match top {
+                    Some(top) if top >= MAX_TOP_DELTA => top.min(MAX_TOP_DELTA),
+                    _ => MAX_TOP_DELTA,
+                };
+                columns.extend(&[step as u8, 0, 0, 0]);
+                top = Some(match top {
+                    Some(top) if top >= MAX_TOP_DELTA => top + step,
+                    _ => step,
+                });
+                delta = top_delta(top, y);
+            }
+            top = Some(y);
+
+            columns.push(delta.unwrap_or_default());
+            columns.push(post.len() as u8);
+            columns.push(0);
+            columns.extend(&post);
+            columns.push(0);
+            y += post.len();
+        }
+        columns.push(0xFF);
+    }
+
+    let mut lump = Vec::with_capacity(columns_start + columns.len());
+    lump.extend(&header.width.to_le_bytes());
+    lump.extend(&header.height.to_le_bytes());
+    lump.extend(&header.left_offset.to_le_bytes());
+    lump.extend(&header.top_offset.to_le_bytes());
+    for offset in column_offsets {
+        let offset = u32::try_from(offset).map_err(|_| "patch too large")?;
+        lump.extend(&offset.to_le_bytes());
+    }
+    lump.extend(columns);
+
+    Ok(lump)
+}
+
+/// The top delta which places a post at `row` after a post at `top`, see `Sprite`
+fn top_delta(top: Option<usize>, row: usize) -> Option<u8> {
+    match top {
+        Some(top) if row <= MAX_TOP_DELTA && row > top => Some(row as u8),
+        None if row <= MAX_TOP_DELTA => Some(row as u8),
+        Some(top) if row - top <= top.min(MAX_TOP_DELTA) => Some((row - top) as u8),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,6 +434,162 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn sprite_validates_post_structure() {
+        // 2x2 patch, both columns share one post covering both rows
+        let mut data = vec![2, 0, 2, 0, 0, 0, 0, 0];
+        data.extend(&16u32.to_le_bytes());
+        data.extend(&16u32.to_le_bytes());
+        data.extend(&[0, 2, 0, 5, 6, 0, 0xFF]);
+        assert!(Sprite::new(&data).unwrap().is_valid());
+
+        // post length exceeds the sprite height
+        data[17] = 3;
+        assert!(!Sprite::new(&data).unwrap().is_valid());
+    }
+
+    /// Palette where color n is (n, n, n)
+    fn gray_palette_data() -> Vec<u8> {
+        (0..768).map(|val: u16| (val / 3) as u8).collect()
+    }
+
+    #[test]
+    fn encode_patch_round_trips_through_decoder() {
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let header = SpriteHeader {
+            width: 3,
+            height: 4,
+            left_offset: -2,
+            top_offset: 7,
+        };
+        // column 0 opaque, column 1 with a gap, column 2 empty
+        let mut rgba = vec![0u8; 3 * 4 * 4];
+        for (x, y) in [(0, 0), (0, 1), (0, 2), (0, 3), (1, 0), (1, 3)] {
+            let value = (x * 10 + y + 1) as u8;
+            rgba[(y * 3 + x) * 4..(y * 3 + x) * 4 + 4].copy_from_slice(&[value, value, value, 255]);
+        }
+
+        let lump = encode_patch(header, &rgba, &palette).unwrap();
+        let sprite = Sprite::new(&lump).unwrap();
+        assert!(sprite.is_valid());
+        assert_eq!(sprite.left_offset(), -2);
+        assert_eq!(sprite.top_offset(), 7);
+        let decoded = sprite.rgba_pixel_buffer(&palette).unwrap();
+        assert_eq!(decoded, rgba);
+        assert_eq!(
+            encode_patch(sprite.header(), &decoded, &palette).unwrap(),
+            lump
+        );
+        // column 1 is split into two posts at the gap
+        assert_eq!(&lump[29..41], &[0, 1, 0, 11, 0, 3, 1, 0, 14, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn encode_patch_splits_long_posts() {
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let header = SpriteHeader {
+            width: 1,
+            height: 255,
+            left_offset: 0,
+            top_offset: 0,
+        };
+        let rgba = [9u8, 9, 9, 255].repeat(255);
+
+        let lump = encode_patch(header, &rgba, &palette).unwrap();
+        assert_eq!(&lump[12..14], &[0, 254]);
+        assert_eq!(&lump[12 + 258..12 + 261], &[254, 1, 0]);
+        let sprite = Sprite::new(&lump).unwrap();
+        assert_eq!(sprite.rgba_pixel_buffer(&palette).unwrap(), rgba);
+    }
+
+    #[test]
+    fn sprite_decodes_tall_patches() {
+        // 1x300 patch with a post at row 200 and one at row 200 + 90, 90 is relative
+        // because it is not larger than the previous top delta
+        let mut data = vec![1, 0, 0x2C, 0x01, 0, 0, 0, 0];
+        data.extend(&12u32.to_le_bytes());
+        data.extend(&[200, 1, 0, 7, 0, 90, 2, 0, 8, 9, 0, 0xFF]);
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+
+        let rgba = Sprite::new(&data)
+            .unwrap()
+            .rgba_pixel_buffer(&palette)
+            .unwrap();
+        assert_eq!(&rgba[200 * 4..201 * 4], &[7, 7, 7, 255]);
+        assert_eq!(&rgba[290 * 4..292 * 4], &[8, 8, 8, 255, 9, 9, 9, 255]);
+        assert_eq!(rgba.chunks(4).filter(|rgba| rgba[3] != 0).count(), 3);
+    }
+
+    #[test]
+    fn sprite_iterates_posts_without_copying() {
+        let mut data = vec![1, 0, 0x2C, 0x01, 0, 0, 0, 0];
+        data.extend(&12u32.to_le_bytes());
+        data.extend(&[200, 1, 0, 7, 0, 90, 2, 0, 8, 9, 0, 0xFF]);
+        let sprite = Sprite::new(&data).unwrap();
+
+        let posts: Vec<Post> = sprite.column(0).unwrap().map(Result::unwrap).collect();
+        assert_eq!(posts.len(), 2);
+        assert_eq!((posts[0].top_delta, posts[0].row), (200, 200));
+        assert_eq!((posts[1].top_delta, posts[1].row), (90, 290));
+        assert_eq!(posts[1].pixels, &[8, 9]);
+        assert!(std::ptr::eq(posts[1].pixels.as_ptr(), &data[20]));
+        assert!(sprite.column(1).is_err());
+
+        // a broken post ends the iteration with an error
+        data[13] = 250;
+        let sprite = Sprite::new(&data).unwrap();
+        let posts: Vec<_> = sprite.columns().next().unwrap().unwrap().collect();
+        assert_eq!(posts.len(), 1);
+        assert!(posts[0].is_err());
+    }
+
+    #[test]
+    fn sprite_decodes_to_indexed_image() {
+        // 2x3 patch, column 0 has rows 0-1, column 1 has row 2
+        let mut data = vec![2, 0, 3, 0, 0, 0, 0, 0];
+        data.extend(&16u32.to_le_bytes());
+        data.extend(&23u32.to_le_bytes());
+        data.extend(&[0, 2, 0, 0, 4, 0, 0xFF, 2, 1, 0, 9, 0, 0xFF]);
+
+        let image = Sprite::new(&data).unwrap().indexed_image().unwrap();
+        assert_eq!(image.pixels(), &[0, 0, 4, 0, 0, 9]);
+        assert_eq!(image.mask(), &[true, false, true, false, false, true]);
+        assert_eq!(image.get(0, 0), Some(0));
+        assert_eq!(image.get(1, 0), None);
+    }
+
+    #[test]
+    fn encode_patch_round_trips_tall_patches() {
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let header = SpriteHeader {
+            width: 2,
+            height: 1200,
+            left_offset: 0,
+            top_offset: 0,
+        };
+        let mut rgba = vec![0u8; 2 * 1200 * 4];
+        for (x, y) in [(0, 10), (0, 700), (0, 1199), (1, 600), (1, 601)] {
+            rgba[(y * 2 + x) * 4..(y * 2 + x) * 4 + 4].copy_from_slice(&[5, 5, 5, 255]);
+        }
+
+        let lump = encode_patch(header, &rgba, &palette).unwrap();
+        let sprite = Sprite::new(&lump).unwrap();
+        assert!(sprite.is_valid());
+        assert_eq!(sprite.rgba_pixel_buffer(&palette).unwrap(), rgba);
+    }
+
+    #[test]
+    fn encode_patch_rejects_mismatching_buffer() {
+        let palette_data = gray_palette_data();
+        let palette = Palette::from_bytes(&palette_data).unwrap();
+        let header = SpriteHeader::from_bytes(&[2, 0, 2, 0, 0, 0, 0, 0]).unwrap();
+        assert!(encode_patch(header, &[0u8; 12], &palette).is_err());
+    }
+
     #[test]
     fn sprite_header_can_extract_header_data() {
         let data = [0x10, 0x00, 0x20, 0x00, 0xFF, 0xFF, 0xEE, 0xFF];
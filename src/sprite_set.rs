@@ -0,0 +1,350 @@
+use crate::index::{LumpNode, LumpSource};
+use crate::lump::LumpRef;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Namespaces which hold sprites, `SS_START` is used by PWADs to add sprites to the IWAD ones
+pub const SPRITE_NAMESPACES: &[&str] = &["S_START", "SS_START"];
+
+/// Highest rotation number, ZDoom extends the 8 rotations of Doom to 16
+pub const MAX_ROTATIONS: usize = 16;
+
+/// A single view of a sprite frame
+#[derive(Debug, Clone)]
+pub struct SpriteRotation<'a> {
+    pub lump_name: Cow<'a, str>,
+    pub lump: LumpRef<'a>,
+    /// The lump has to be drawn flipped horizontally
+    pub mirrored: bool,
+}
+
+/// All rotations of a single frame of a sprite
+#[derive(Debug, Clone, Default)]
+pub struct SpriteFrame<'a> {
+    all_angles: Option<SpriteRotation<'a>>,
+    rotations: [Option<SpriteRotation<'a>>; MAX_ROTATIONS],
+}
+
+impl<'a> SpriteFrame<'a> {
+    /// Returns the view for a rotation.
+    ///
+    /// # Arguments
+    /// - `rotation`: The rotation number of the lump name, 1-8 (or 1-16 for ZDoom sprites),
+    ///   1 faces the viewer. The numbers are no sequential angles: rotations 1-8 step
+    ///   45 degrees around the thing, the ZDoom rotations 9-16 (`9`-`G` in lump names)
+    ///   sit halfway between them, so the 16 views in angle order are
+    ///   1, 9, 2, 10, 3, 11, 4, 12, 5, 13, 6, 14, 7, 15, 8, 16.
+    /// # Returns
+    /// - `Some(SpriteRotation)` with the lump of the rotation, or the rotation 0 lump
+    ///   if the frame looks the same from all angles.
+    /// - `None` if the frame has no lump for the rotation.
+    pub fn rotation(&self, rotation: usize) -> Option<SpriteRotation<'a>> {
+        rotation
+            .checked_sub(1)
+            .and_then(|index| self.rotations.get(index).cloned().flatten())
+            .or_else(|| self.all_angles.clone())
+    }
+
+    /// `true` if the frame is drawn with the same lump from all angles (rotation 0)
+    pub fn is_all_angles(&self) -> bool {
+        self.all_angles.is_some() && self.rotations.iter().all(Option::is_none)
+    }
+
+    /// Number of rotations the frame was made for: 1, 8 or 16
+    pub fn rotation_count(&self) -> usize {
+        if self.rotations[8..].iter().any(Option::is_some) {
+            16
+        } else if self.rotations.iter().any(Option::is_some) {
+            8
+        } else {
+            1
+        }
+    }
+
+    /// Installs a view the way ZDoom's `R_InstallSpriteLump` does: a rotation 0 lump
+    /// replaces all rotations, a rotated lump replaces a previous rotation 0 lump.
+    /// Views are inserted in lump order, so the later lump wins. Vanilla Doom aborts with
+    /// an error on such frames instead.
+    fn insert(&mut self, rotation: usize, view: SpriteRotation<'a>) {
+        match rotation {
+            0 => {
+                self.rotations = Default::default();
+                self.all_angles = Some(view);
+            }
+            _ => {
+                self.all_angles = None;
+                self.rotations[rotation - 1] = Some(view);
+            }
+        }
+    }
+}
+
+/// Parses a frame character, A-Z plus the ZDoom extensions '[', '\' and ']'
+fn parse_frame(frame: u8) -> Option<char> {
+    (b'A'..=b']').contains(&frame).then_some(frame as char)
+}
+
+/// Parses a rotation character, '0'-'9' and 'A'-'G' for the 16 rotations of ZDoom
+fn parse_rotation(rotation: u8) -> Option<usize> {
+    match rotation {
+        b'0'..=b'9' => Some((rotation - b'0') as usize),
+        b'A'..=b'G' => Some((rotation - b'A') as usize + 10),
+        _ => None,
+    }
+}
+
+/// A frame and rotation encoded in a sprite lump name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteView {
+    pub frame: char,
+    pub rotation: usize,
+    pub mirrored: bool,
+}
+
+/// Splits a sprite lump name into the prefix and its views.
+///
+/// # Examples
+/// - `TROOA1`: prefix `TROO`, frame A, rotation 1
+/// - `POSSA2A8`: prefix `POSS`, frame A, rotation 2 and rotation 8 mirrored
+pub fn parse_sprite_name(name: &str) -> Option<(&str, Vec<SpriteView>)> {
+    let bytes = name.as_bytes();
+    if !name.is_ascii() || (bytes.len() != 6 && bytes.len() != 8) {
+        return None;
+    }
+
+    let mut views = Vec::with_capacity(2);
+    for (i, pair) in bytes[4..].chunks_exact(2).enumerate() {
+        views.push(SpriteView {
+            frame: parse_frame(pair[0])?,
+            rotation: parse_rotation(pair[1])?,
+            mirrored: i == 1,
+        });
+    }
+
+    Some((&name[..4], views))
+}
+
+/// Sprites of a WAD grouped by prefix and frame.
+///
+/// Sprite lump names consist of a 4 character prefix, a frame letter and a rotation number,
+/// optionally followed by a second frame and rotation which use the same lump mirrored.
+/// Rotation 0 means the frame looks the same from all angles.
+///
+/// # Example
+/// `set.get("TROO", 'C', 3)` returns the lump to draw for frame C of the imp seen from
+/// rotation 3.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteSet<'a> {
+    sprites: HashMap<String, HashMap<char, SpriteFrame<'a>>>,
+}
+
+impl<'a> SpriteSet<'a> {
+    /// Collects the sprites of the namespaces of `SPRITE_NAMESPACES` including nested
+    /// namespaces. Lumps with names that are no valid sprite names are skipped.
+    ///
+    /// Lumps are inserted in directory order, so if two lumps claim the same frame and
+    /// rotation (e.g. `POSSA2A8` and `POSSA8`) the later one wins like in the engine.
+    pub fn from_source<S: LumpSource<'a>>(source: &S) -> Self {
+        let mut lumps = Vec::new();
+        for namespace in SPRITE_NAMESPACES {
+            if let Some(LumpNode::Namespace { children, .. }) = source.get_lump(vec![], namespace) {
+                collect(children, &mut lumps);
+            }
+        }
+        lumps.sort_by(|(a_name, a), (b_name, b)| {
+            a.entry_index()
+                .cmp(&b.entry_index())
+                .then_with(|| a_name.cmp(b_name))
+        });
+
+        let mut set = Self::default();
+        for (name, lump) in lumps {
+            set.insert(name, lump);
+        }
+
+        set
+    }
+
+    /// Adds a sprite lump, returns `false` if the name is no valid sprite name
+    pub fn insert<N: Into<Cow<'a, str>>>(&mut self, name: N, lump: LumpRef<'a>) -> bool {
+        let name = name.into();
+        let Some((prefix, views)) = parse_sprite_name(&name) else {
+            return false;
+        };
+
+        let frames = self.sprites.entry(prefix.to_string()).or_default();
+        for view in views {
+            let rotation = SpriteRotation {
+                lump_name: name.clone(),
+                lump: lump.clone(),
+                mirrored: view.mirrored,
+            };
+            frames
+                .entry(view.frame)
+                .or_default()
+                .insert(view.rotation, rotation);
+        }
+
+        true
+    }
+
+    /// Returns the view of a sprite frame for a rotation, see `SpriteFrame::rotation`
+    pub fn get(&self, prefix: &str, frame: char, rotation: usize) -> Option<SpriteRotation<'a>> {
+        self.frame(prefix, frame)?.rotation(rotation)
+    }
+
+    pub fn frame(&self, prefix: &str, frame: char) -> Option<&SpriteFrame<'a>> {
+        self.sprites.get(prefix)?.get(&frame)
+    }
+
+    /// The frame letters of a sprite in alphabetical order
+    pub fn frames(&self, prefix: &str) -> Vec<char> {
+        let mut frames: Vec<char> = self
+            .sprites
+            .get(prefix)
+            .map(|frames| frames.keys().copied().collect())
+            .unwrap_or_default();
+        frames.sort();
+        frames
+    }
+
+    /// The sprite prefixes in alphabetical order
+    pub fn prefixes(&self) -> Vec<&str> {
+        let mut prefixes: Vec<&str> = self.sprites.keys().map(String::as_str).collect();
+        prefixes.sort();
+        prefixes
+    }
+}
+
+/// Gathers the lumps of a namespace and its nested namespaces
+fn collect<'a>(
+    children: &HashMap<Cow<'a, str>, LumpNode<'a>>,
+    lumps: &mut Vec<(Cow<'a, str>, LumpRef<'a>)>,
+) {
+    for node in children.values() {
+        match node {
+            LumpNode::Namespace { children, .. } => collect(children, lumps),
+            LumpNode::Lump { name, lump } => lumps.push((name.clone(), lump.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::wad::{WadIndex, WadWriter};
+
+    fn view(frame: char, rotation: usize, mirrored: bool) -> SpriteView {
+        SpriteView {
+            frame,
+            rotation,
+            mirrored,
+        }
+    }
+
+    #[test]
+    fn sprite_names_can_be_parsed() {
+        assert_eq!(
+            parse_sprite_name("TROOA1"),
+            Some(("TROO", vec![view('A', 1, false)]))
+        );
+        assert_eq!(
+            parse_sprite_name("POSSA2A8"),
+            Some(("POSS", vec![view('A', 2, false), view('A', 8, true)]))
+        );
+        assert_eq!(
+            parse_sprite_name("VILE[1"),
+            Some(("VILE", vec![view('[', 1, false)]))
+        );
+        assert_eq!(
+            parse_sprite_name("ZOMBA9AG"),
+            Some(("ZOMB", vec![view('A', 9, false), view('A', 16, true)]))
+        );
+        assert!(parse_sprite_name("TROOA").is_none());
+        assert!(parse_sprite_name("TROOAX").is_none());
+        assert!(parse_sprite_name("TROOa1").is_none());
+    }
+
+    #[test]
+    fn sprite_set_resolves_rotations_and_mirrors() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_marker("S_START").unwrap();
+        wad.add_lump("TROOA1", &[1u8][..]).unwrap();
+        wad.add_lump("TROOA2A8", &[2u8][..]).unwrap();
+        wad.add_lump("TROOI0", &[3u8][..]).unwrap();
+        wad.add_lump("TROOPER", &[4u8][..]).unwrap();
+        wad.add_marker("S_END").unwrap();
+        wad.add_marker("SS_START").unwrap();
+        wad.add_lump("BAL1A0", &[5u8][..]).unwrap();
+        wad.add_marker("SS_END").unwrap();
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let set = SpriteSet::from_source(&index);
+
+        assert_eq!(set.prefixes(), vec!["BAL1", "TROO"]);
+        assert_eq!(set.frames("TROO"), vec!['A', 'I']);
+
+        let view = set.get("TROO", 'A', 8).unwrap();
+        assert_eq!(view.lump_name, "TROOA2A8");
+        assert!(view.mirrored);
+        assert!(!set.get("TROO", 'A', 2).unwrap().mirrored);
+        assert!(set.get("TROO", 'A', 3).is_none());
+        assert_eq!(set.frame("TROO", 'A').unwrap().rotation_count(), 8);
+
+        // rotation 0 serves every angle
+        let frame = set.frame("TROO", 'I').unwrap();
+        assert!(frame.is_all_angles());
+        assert_eq!(frame.rotation(5).unwrap().lump_name, "TROOI0");
+        assert_eq!(set.get("BAL1", 'A', 1).unwrap().lump.data(), &[5]);
+    }
+
+    #[test]
+    fn later_lumps_win_in_directory_order() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_marker("S_START").unwrap();
+        wad.add_lump("POSSA2A8", &[1u8][..]).unwrap();
+        wad.add_lump("POSSA8", &[2u8][..]).unwrap();
+        wad.add_lump("SARGA8", &[3u8][..]).unwrap();
+        wad.add_lump("SARGA2A8", &[4u8][..]).unwrap();
+        wad.add_marker("S_END").unwrap();
+        let data = wad.finish().unwrap();
+
+        // the result must not depend on the iteration order of the lump tree
+        for _ in 0..8 {
+            let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+            let set = SpriteSet::from_source(&index);
+
+            let view = set.get("POSS", 'A', 8).unwrap();
+            assert_eq!(view.lump_name, "POSSA8");
+            assert!(!view.mirrored);
+            assert_eq!(set.get("POSS", 'A', 2).unwrap().lump_name, "POSSA2A8");
+
+            let view = set.get("SARG", 'A', 8).unwrap();
+            assert_eq!(view.lump_name, "SARGA2A8");
+            assert!(view.mirrored);
+        }
+    }
+
+    #[test]
+    fn rotation_zero_and_rotations_replace_each_other() {
+        let mut set = SpriteSet::default();
+        set.insert("TROOA0", LumpRef::new(&[1], "TROOA0"));
+        set.insert("TROOA1", LumpRef::new(&[2], "TROOA1"));
+        set.insert("TROOA2A8", LumpRef::new(&[3], "TROOA2A8"));
+
+        // rotated lumps after A0 drop it, missing rotations stay missing
+        let frame = set.frame("TROO", 'A').unwrap();
+        assert!(!frame.is_all_angles());
+        assert_eq!(frame.rotation(1).unwrap().lump_name, "TROOA1");
+        assert!(frame.rotation(3).is_none());
+
+        // a later A0 replaces all rotations
+        set.insert("TROOA0", LumpRef::new(&[4], "TROOA0"));
+        let frame = set.frame("TROO", 'A').unwrap();
+        assert!(frame.is_all_angles());
+        assert_eq!(frame.rotation(1).unwrap().lump.data(), &[4]);
+        assert_eq!(frame.rotation(8).unwrap().lump.data(), &[4]);
+        assert_eq!(frame.rotation_count(), 1);
+    }
+}
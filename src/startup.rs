@@ -0,0 +1,245 @@
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Width of the Hexen startup screen
+pub const STARTUP_WIDTH: usize = 640;
+/// Height of the Hexen startup screen
+pub const STARTUP_HEIGHT: usize = 480;
+/// Number of colors of the startup palette
+pub const STARTUP_COLORS: usize = 16;
+
+const PALETTE_SIZE: usize = STARTUP_COLORS * 3;
+const PLANES: usize = 4;
+const PLANE_SIZE: usize = STARTUP_WIDTH * STARTUP_HEIGHT / 8;
+/// Size of a STARTUP lump, the palette followed by the 4 bit planes
+pub const STARTUP_SIZE: usize = PALETTE_SIZE + PLANES * PLANE_SIZE;
+
+/// Size of the progress notch of the single player startup (NOTCH)
+pub const NOTCH_SIZE: (usize, usize) = (16, 23);
+/// Size of the progress notch of the network startup (NETNOTCH)
+pub const NET_NOTCH_SIZE: (usize, usize) = (4, 16);
+
+/// The 16 color palette of a startup screen
+pub type StartupPalette = [[u8; 3]; STARTUP_COLORS];
+
+/// Represents the STARTUP lump of Hexen, the screen shown while the game loads.
+///
+/// # Structure of a STARTUP Lump
+/// - 48 bytes: 16 palette colors, 3 bytes each with 6 bit VGA DAC values (0-63)
+/// - 153600 bytes: 4 bit planes of 38400 bytes in the layout of the 640x480 16 color
+///   VGA mode. Plane n holds bit n of the color index of every pixel, row by row with
+///   the leftmost pixel in the highest bit of each byte.
+#[derive(Debug, Clone)]
+pub struct StartupScreen<'a> {
+    palette: StartupPalette,
+    planes: &'a [u8],
+}
+
+impl<'a> StartupScreen<'a> {
+    /// Creates a `StartupScreen` from the lump data.
+    ///
+    /// # Arguments
+    /// - `data`: The STARTUP lump data.
+    /// # Returns
+    /// - `Ok(StartupScreen)` with the palette scaled to 8 bit values.
+    /// - `Err` if the lump is shorter than 153648 bytes.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        if data.len() < STARTUP_SIZE {
+            return Err("Startup screen data too short".into());
+        }
+
+        let mut palette = [[0u8; 3]; STARTUP_COLORS];
+        for (color, dac) in palette.iter_mut().zip(data.chunks_exact(3)) {
+            for (channel, &value) in color.iter_mut().zip(dac) {
+                *channel = vga_dac_to_rgb(value);
+            }
+        }
+
+        Ok(Self {
+            palette,
+            planes: &data[PALETTE_SIZE..STARTUP_SIZE],
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        STARTUP_WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        STARTUP_HEIGHT
+    }
+
+    /// The palette of the screen with 8 bit values, also used for the notches
+    pub fn palette(&self) -> &StartupPalette {
+        &self.palette
+    }
+
+    /// Combines the bit planes into one color index (0-15) per pixel, row by row
+    pub fn indexed_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; STARTUP_WIDTH * STARTUP_HEIGHT];
+        for (plane, bits) in self.planes.chunks_exact(PLANE_SIZE).enumerate() {
+            for (pixel_group, &byte) in pixels.chunks_exact_mut(8).zip(bits) {
+                for (bit, pixel) in pixel_group.iter_mut().enumerate() {
+                    if byte & (0x80 >> bit) != 0 {
+                        *pixel |= 1 << plane;
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Converts the screen into an RGBA pixel buffer with `640 * 480 * 4` bytes
+    pub fn rgba_pixel_buffer(&self) -> Vec<u8> {
+        indexed_to_rgba(&self.indexed_pixels(), &self.palette)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for StartupScreen<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> std::result::Result<Self, Self::Error> {
+        StartupScreen::from_bytes(value)
+    }
+}
+
+/// Represents the NOTCH and NETNOTCH lumps, the progress markers drawn over the startup
+/// screen while Hexen loads.
+///
+/// # Structure of a Notch Lump
+/// Unlike the STARTUP screen the notches are not planar, they are packed 4 bit color
+/// indices into the startup palette, two pixels per byte with the left pixel in the high
+/// nibble. The lumps have no header, NOTCH is 16x23 and NETNOTCH is 4x16 pixels.
+#[derive(Debug, Clone)]
+pub struct NotchBitmap<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> NotchBitmap<'a> {
+    /// Creates a `NotchBitmap` with the given dimensions.
+    ///
+    /// # Arguments
+    /// - `data`: The lump data.
+    /// - `width`: The width in pixels, must be even.
+    /// - `height`: The height in pixels.
+    /// # Returns
+    /// - `Ok(NotchBitmap)` if the lump holds all pixels.
+    /// - `Err` if the width is odd or the lump is too short.
+    pub fn new(data: &'a [u8], width: usize, height: usize) -> Result<Self> {
+        if !width.is_multiple_of(2) {
+            return Err("Notch width must be even".into());
+        }
+        let size = width * height / 2;
+        let data = data.get(..size).ok_or("Notch data too short")?;
+
+        Ok(Self {
+            data,
+            width,
+            height,
+        })
+    }
+
+    /// Decodes the NOTCH lump of the single player startup
+    pub fn notch(data: &'a [u8]) -> Result<Self> {
+        Self::new(data, NOTCH_SIZE.0, NOTCH_SIZE.1)
+    }
+
+    /// Decodes the NETNOTCH lump of the network startup
+    pub fn net_notch(data: &'a [u8]) -> Result<Self> {
+        Self::new(data, NET_NOTCH_SIZE.0, NET_NOTCH_SIZE.1)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The color indices (0-15) of the bitmap, row by row
+    pub fn indexed_pixels(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|&byte| [byte >> 4, byte & 0x0F])
+            .collect()
+    }
+
+    /// Converts the bitmap into an RGBA pixel buffer with the palette of the startup screen
+    pub fn rgba_pixel_buffer(&self, palette: &StartupPalette) -> Vec<u8> {
+        indexed_to_rgba(&self.indexed_pixels(), palette)
+    }
+}
+
+/// Scales a 6 bit VGA DAC value to 8 bits
+fn vga_dac_to_rgb(value: u8) -> u8 {
+    let value = value & 0x3F;
+    (value << 2) | (value >> 4)
+}
+
+fn indexed_to_rgba(pixels: &[u8], palette: &StartupPalette) -> Vec<u8> {
+    let mut pixel_buffer = vec![0u8; pixels.len() * 4];
+    for (&index, rgba) in pixels.iter().zip(pixel_buffer.chunks_exact_mut(4)) {
+        let [r, g, b] = palette[index as usize];
+        rgba.copy_from_slice(&[r, g, b, 255]);
+    }
+
+    pixel_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Startup lump where color n is (n * 4, 0, 63) and the first pixel uses color 5
+    fn startup_lump() -> Vec<u8> {
+        let mut data = vec![0u8; STARTUP_SIZE];
+        for color in 0..STARTUP_COLORS {
+            data[color * 3..color * 3 + 3].copy_from_slice(&[color as u8 * 4, 0, 63]);
+        }
+        // color 5 = planes 0 and 2
+        data[PALETTE_SIZE] = 0x80;
+        data[PALETTE_SIZE + 2 * PLANE_SIZE] = 0x80;
+        // the last pixel uses color 8 = plane 3
+        data[STARTUP_SIZE - 1] = 0x01;
+        data
+    }
+
+    #[test]
+    fn startup_screen_combines_planes() {
+        assert!(StartupScreen::from_bytes(&[0u8; 1000]).is_err());
+
+        let data = startup_lump();
+        let screen = StartupScreen::from_bytes(&data).unwrap();
+        assert_eq!(screen.palette()[5], [81, 0, 255]);
+
+        let pixels = screen.indexed_pixels();
+        assert_eq!(pixels.len(), 640 * 480);
+        assert_eq!(&pixels[0..2], &[5, 0]);
+        assert_eq!(pixels[pixels.len() - 1], 8);
+
+        let rgba = screen.rgba_pixel_buffer();
+        assert_eq!(&rgba[0..4], &[81, 0, 255, 255]);
+    }
+
+    #[test]
+    fn notch_bitmaps_unpack_nibbles() {
+        let data = startup_lump();
+        let screen = StartupScreen::from_bytes(&data).unwrap();
+
+        let notch_data: Vec<u8> = (0..184).map(|i| i as u8).collect();
+        let notch = NotchBitmap::notch(&notch_data).unwrap();
+        assert_eq!((notch.width(), notch.height()), (16, 23));
+        assert_eq!(&notch.indexed_pixels()[2..4], &[0, 1]);
+
+        let rgba = notch.rgba_pixel_buffer(screen.palette());
+        assert_eq!(rgba.len(), 16 * 23 * 4);
+        assert_eq!(&rgba[12..16], &[16, 0, 255, 255]);
+
+        assert!(NotchBitmap::net_notch(&[0u8; 31]).is_err());
+        assert!(NotchBitmap::new(&[0u8; 32], 3, 2).is_err());
+    }
+}
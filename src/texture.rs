@@ -0,0 +1,684 @@
+use crate::graphics::IndexedImage;
+use crate::index::{LumpNode, LumpSource};
+use crate::lump::{LUMP_NAME_LENGTH, LumpRef};
+use crate::sprite::Sprite;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Layout of the texture definitions in a TEXTUREx lump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// Doom, Heretic and Hexen
+    Doom,
+    /// Strife, without the column directory and the unused patch fields
+    Strife,
+    /// The Doom 0.4 alpha, the Doom layout without the texture name
+    Nameless,
+}
+
+impl TextureFormat {
+    /// Size of the name at the start of a texture definition
+    fn name_size(self) -> usize {
+        match self {
+            TextureFormat::Doom | TextureFormat::Strife => LUMP_NAME_LENGTH,
+            TextureFormat::Nameless => 0,
+        }
+    }
+
+    /// Size of a texture definition without its patches
+    fn map_texture_size(self) -> usize {
+        match self {
+            TextureFormat::Doom => 22,
+            TextureFormat::Strife => 18,
+            TextureFormat::Nameless => 14,
+        }
+    }
+
+    /// Size of a patch placement inside a texture definition
+    fn map_patch_size(self) -> usize {
+        match self {
+            TextureFormat::Doom | TextureFormat::Nameless => 10,
+            TextureFormat::Strife => 6,
+        }
+    }
+
+    /// Offset of the patch count inside a texture definition
+    fn patch_count_offset(self) -> usize {
+        self.map_texture_size() - 2
+    }
+
+    /// Detects the layout of a TEXTUREx lump.
+    ///
+    /// The lump has no version field, so the layout is guessed from the definitions. The
+    /// definitions are stored back to back, so in the right layout every definition has a
+    /// positive size and a patch count of at least zero, and ends exactly where the next
+    /// definition (or the lump) starts. Definitions without patches are valid. Only the
+    /// nameless layout accepts definitions which don't start with a printable name.
+    /// Doom is checked before Strife, and Strife before the nameless layout.
+    ///
+    /// # Returns
+    /// - `Ok(TextureFormat)` with the detected layout.
+    /// - `Err` if the lump matches none of the layouts.
+    pub fn detect(data: &[u8]) -> Result<Self> {
+        const FORMATS: [TextureFormat; 3] = [
+            TextureFormat::Doom,
+            TextureFormat::Strife,
+            TextureFormat::Nameless,
+        ];
+
+        let offsets = definition_offsets(data)?;
+        let mut boundaries = offsets.clone();
+        boundaries.push(data.len());
+        boundaries.sort_unstable();
+        let next_boundary = |offset: usize| {
+            boundaries
+                .iter()
+                .copied()
+                .find(|&boundary| boundary > offset)
+                .unwrap_or(data.len())
+        };
+
+        FORMATS
+            .into_iter()
+            .find(|&format| {
+                offsets.iter().all(|&offset| {
+                    format.definition_end(data, offset) == Some(next_boundary(offset))
+                })
+            })
+            .ok_or_else(|| "TEXTUREx lump matches none of the known layouts".into())
+    }
+
+    /// Returns the end of the definition at `offset` if it is valid in this layout
+    fn definition_end(self, data: &[u8], offset: usize) -> Option<usize> {
+        let field = |at: usize| read_i16(data, offset + self.name_size() + at).ok();
+        let width = field(4)?;
+        let height = field(6)?;
+        let patch_count = read_i16(data, offset + self.patch_count_offset()).ok()?;
+        if width <= 0 || height <= 0 || patch_count < 0 {
+            return None;
+        }
+        if (self.name_size() > 0) != has_printable_name(data, offset) {
+            return None;
+        }
+
+        let end = offset + self.map_texture_size() + patch_count as usize * self.map_patch_size();
+        (end <= data.len()).then_some(end)
+    }
+}
+
+/// `true` if a non-empty, printable ASCII name starts at `at`
+fn has_printable_name(data: &[u8], at: usize) -> bool {
+    let Some(bytes) = data.get(at..at + LUMP_NAME_LENGTH) else {
+        return false;
+    };
+    let length = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(LUMP_NAME_LENGTH);
+    length > 0 && bytes[..length].iter().all(|b| b.is_ascii_graphic())
+}
+
+/// Reads the offset table at the start of a TEXTUREx lump
+fn definition_offsets(data: &[u8]) -> Result<Vec<usize>> {
+    let count = read_i32(data, 0)?;
+    if count < 0 {
+        return Err("Negative number of textures".into());
+    }
+
+    (0..count as usize)
+        .map(|i| {
+            let offset = read_i32(data, 4 + i * 4)?;
+            usize::try_from(offset).map_err(|_| "Negative texture offset".into())
+        })
+        .collect()
+}
+
+/// Namespaces searched for patches before the top level of the WAD
+const PATCH_NAMESPACES: &[&str] = &["P_START", "PP_START"];
+
+fn read_i16(data: &[u8], at: usize) -> Result<i16> {
+    let bytes = data
+        .get(at..at + 2)
+        .ok_or("Unexpected end of texture data")?;
+    Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_i32(data: &[u8], at: usize) -> Result<i32> {
+    let bytes = data
+        .get(at..at + 4)
+        .ok_or("Unexpected end of texture data")?;
+    Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads a zero padded 8 character name, names are upper cased like lump names
+fn read_name(data: &[u8], at: usize) -> Result<String> {
+    let bytes = data
+        .get(at..at + LUMP_NAME_LENGTH)
+        .ok_or("Unexpected end of texture data")?;
+    let length = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(LUMP_NAME_LENGTH);
+    Ok(std::str::from_utf8(&bytes[..length])?.to_ascii_uppercase())
+}
+
+/// Represents a PNAMES lump, the list of patches textures refer to by index.
+///
+/// # Structure of a PNAMES Lump
+/// - 4 bytes: Number of names (i32, little-endian)
+/// - N * 8 bytes: Zero padded patch lump names
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchNames {
+    names: Vec<String>,
+}
+
+impl PatchNames {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let count = read_i32(data, 0)?;
+        if count < 0 {
+            return Err("Negative number of patch names".into());
+        }
+
+        let names = (0..count as usize)
+            .map(|i| read_name(data, 4 + i * LUMP_NAME_LENGTH))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { names })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(String::as_str)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl TryFrom<&[u8]> for PatchNames {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        PatchNames::from_bytes(value)
+    }
+}
+
+/// Placement of a patch inside a texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexturePatch {
+    pub origin_x: i16,
+    pub origin_y: i16,
+    /// Index into PNAMES
+    pub patch: u16,
+}
+
+/// A wall texture definition from a TEXTURE1/TEXTURE2 lump.
+///
+/// # Structure of a TEXTUREx Lump
+/// - 4 bytes: Number of textures (i32, little-endian)
+/// - N * 4 bytes: Offsets of the texture definitions (i32, little-endian)
+///
+/// Each texture definition (maptexture) consists of:
+/// - 8 bytes: Zero padded texture name
+/// - 4 bytes: Masked flag (i32, unused by the engine)
+/// - 2 bytes: Width (i16, little-endian)
+/// - 2 bytes: Height (i16, little-endian)
+/// - 4 bytes: Column directory (unused)
+/// - 2 bytes: Number of patches (i16, little-endian)
+/// - N * 10 bytes: Patches (mappatch), each with origin x and y (i16), the PNAMES index (i16)
+///   and the unused step direction and colormap fields (i16)
+///
+/// Strife uses a shorter layout (see `TextureFormat`): the column directory is missing and
+/// each patch is only 6 bytes, without the step direction and colormap fields. The Doom 0.4
+/// alpha leaves out the texture name, those textures are named by their position in the
+/// lump, e.g. `TEX0005`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDefinition {
+    pub name: String,
+    pub masked: bool,
+    pub width: u16,
+    pub height: u16,
+    pub patches: Vec<TexturePatch>,
+}
+
+/// Result of composing a texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComposedTexture {
+    pub image: IndexedImage,
+    /// Names of patches which are not in the WAD, the texture is composed without them
+    pub missing_patches: Vec<String>,
+}
+
+impl TextureDefinition {
+    /// Parses every texture definition of a TEXTURE1 or TEXTURE2 lump,
+    /// the layout is detected with `TextureFormat::detect`.
+    ///
+    /// # Arguments
+    /// - `data`: The TEXTUREx lump data.
+    /// # Returns
+    /// - `Ok(Vec<TextureDefinition>)` in lump order.
+    /// - `Err` if a definition is out of range or has invalid values.
+    pub fn parse_lump(data: &[u8]) -> Result<Vec<Self>> {
+        Self::parse_lump_with_format(data, TextureFormat::detect(data)?)
+    }
+
+    /// Parses every texture definition of a TEXTUREx lump with a known layout
+    pub fn parse_lump_with_format(data: &[u8], format: TextureFormat) -> Result<Vec<Self>> {
+        definition_offsets(data)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, offset)| Self::parse_definition(data, index, offset, format))
+            .collect()
+    }
+
+    fn parse_definition(
+        data: &[u8],
+        index: usize,
+        offset: usize,
+        format: TextureFormat,
+    ) -> Result<Self> {
+        let name = match format {
+            TextureFormat::Nameless => format!("TEX{:04}", index),
+            _ => read_name(data, offset)?,
+        };
+        let fields = offset + format.name_size();
+        let masked = read_i32(data, fields)? != 0;
+        let width = read_i16(data, fields + 4)?;
+        let height = read_i16(data, fields + 6)?;
+        let patch_count = read_i16(data, offset + format.patch_count_offset())?;
+        if width <= 0 || height <= 0 || patch_count < 0 {
+            return Err(format!("Texture '{}' has an invalid size", name).into());
+        }
+
+        let patches = (0..patch_count as usize)
+            .map(|i| {
+                let at = offset + format.map_texture_size() + i * format.map_patch_size();
+                Ok(TexturePatch {
+                    origin_x: read_i16(data, at)?,
+                    origin_y: read_i16(data, at + 2)?,
+                    patch: read_i16(data, at + 4)? as u16,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name,
+            masked,
+            width: width as u16,
+            height: height as u16,
+            patches,
+        })
+    }
+
+    /// Draws the patches of the texture into an indexed image.
+    ///
+    /// Patches are drawn in definition order, so later patches cover earlier ones.
+    /// Like the vanilla renderer, posts reaching above the top of the texture are not
+    /// shifted but cut off at the bottom, i.e. a negative y origin only shortens the post.
+    /// Patches are looked up in the `P_START` and `PP_START` namespaces first and then at
+    /// the top level of the lump tree.
+    ///
+    /// # Arguments
+    /// - `patch_names`: The PNAMES lump the patch indices refer to.
+    /// - `source`: The lump tree holding the patches.
+    /// # Returns
+    /// - `Ok(ComposedTexture)` with the image and the names of missing patches.
+    /// - `Err` if a patch index is not in PNAMES or a patch lump is invalid.
+    pub fn compose<'a, S: LumpSource<'a>>(
+        &self,
+        patch_names: &PatchNames,
+        source: &S,
+    ) -> Result<ComposedTexture> {
+        let width = self.width as usize;
+        let mut image = IndexedImage::new(width, self.height as usize);
+        let mut missing_patches: Vec<String> = Vec::new();
+
+        for placement in &self.patches {
+            let name = patch_names.get(placement.patch as usize).ok_or_else(|| {
+                format!(
+                    "Texture '{}' uses patch {} which is not in PNAMES",
+                    self.name, placement.patch
+                )
+            })?;
+            let Some(lump) = find_patch(source, name) else {
+                if !missing_patches.iter().any(|missing| missing == name) {
+                    missing_patches.push(name.to_string());
+                }
+                continue;
+            };
+
+            let data = lump.decompressed()?;
+            let patch =
+                Sprite::new(&data).map_err(|e| format!("Patch '{}' is invalid: {}", name, e))?;
+            patch.for_each_post(|column, row_start, pixels| {
+                let x = placement.origin_x as i64 + column as i64;
+                if x < 0 || x >= width as i64 {
+                    return Ok(());
+                }
+
+                let y = placement.origin_y as i64 + row_start as i64;
+                let (y, pixels) = if y < 0 {
+                    let length = pixels.len().saturating_sub(y.unsigned_abs() as usize);
+                    (0, &pixels[..length])
+                } else {
+                    (y as usize, pixels)
+                };
+                for (dy, &index) in pixels.iter().enumerate() {
+                    image.set(x as usize, y + dy, index);
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(ComposedTexture {
+            image,
+            missing_patches,
+        })
+    }
+}
+
+/// Parses the definitions of TEXTURE1 and TEXTURE2 (if present) of a lump tree
+pub fn texture_definitions<'a, S: LumpSource<'a>>(source: &S) -> Result<Vec<TextureDefinition>> {
+    let mut definitions = Vec::new();
+    for name in ["TEXTURE1", "TEXTURE2"] {
+        if let Some(LumpNode::Lump { lump, .. }) = source.get_lump(vec![], name) {
+            definitions.extend(TextureDefinition::parse_lump(&lump.decompressed()?)?);
+        }
+    }
+
+    Ok(definitions)
+}
+
+fn find_patch<'a, S: LumpSource<'a>>(source: &S, name: &str) -> Option<LumpRef<'a>> {
+    PATCH_NAMESPACES
+        .iter()
+        .filter_map(|namespace| match source.get_lump(vec![], namespace) {
+            Some(LumpNode::Namespace { children, .. }) => find_in_namespace(children, name),
+            _ => None,
+        })
+        .next()
+        .or_else(|| match source.get_lump(vec![], name) {
+            Some(LumpNode::Lump { lump, .. }) => Some(lump.clone()),
+            _ => None,
+        })
+}
+
+/// Searches a namespace and its nested namespaces (`P1_START`, ...)
+fn find_in_namespace<'a>(
+    children: &HashMap<Cow<'a, str>, LumpNode<'a>>,
+    name: &str,
+) -> Option<LumpRef<'a>> {
+    if let Some(LumpNode::Lump { lump, .. }) = children.get(name) {
+        return Some(lump.clone());
+    }
+
+    children.values().find_map(|node| match node {
+        LumpNode::Namespace { children, .. } => find_in_namespace(children, name),
+        LumpNode::Lump { .. } => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::wad::{WadIndex, WadWriter};
+
+    /// Builds a patch with a single post per column covering rows `top..top + length`
+    fn patch(width: u16, height: u16, top: u8, length: u8, index: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(&width.to_le_bytes());
+        data.extend(&height.to_le_bytes());
+        data.extend(&[0, 0, 0, 0]);
+        let columns_start = 8 + width as usize * 4;
+        let column_size = 5 + length as usize;
+        for column in 0..width as usize {
+            data.extend(&((columns_start + column * column_size) as u32).to_le_bytes());
+        }
+        for _ in 0..width {
+            data.extend(&[top, length, 0]);
+            data.extend(vec![index; length as usize]);
+            data.extend(&[0, 0xFF]);
+        }
+        data
+    }
+
+    fn pnames(names: &[&str]) -> Vec<u8> {
+        let mut data = (names.len() as i32).to_le_bytes().to_vec();
+        for name in names {
+            let mut bytes = [0u8; 8];
+            bytes[..name.len()].copy_from_slice(name.as_bytes());
+            data.extend(&bytes);
+        }
+        data
+    }
+
+    /// Builds a TEXTURE1 lump with a single texture
+    fn texture1(name: &str, width: i16, height: i16, patches: &[(i16, i16, i16)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(&1i32.to_le_bytes());
+        data.extend(&8i32.to_le_bytes());
+        let mut bytes = [0u8; 8];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        data.extend(&bytes);
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&width.to_le_bytes());
+        data.extend(&height.to_le_bytes());
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&(patches.len() as i16).to_le_bytes());
+        for (x, y, patch) in patches {
+            data.extend(&x.to_le_bytes());
+            data.extend(&y.to_le_bytes());
+            data.extend(&patch.to_le_bytes());
+            data.extend(&[1, 0, 0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn patch_names_can_be_parsed() {
+        let names = PatchNames::from_bytes(&pnames(&["WALL00_1", "w94_1"])).unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.get(1), Some("W94_1"));
+        assert!(PatchNames::from_bytes(&[2, 0, 0, 0, b'A']).is_err());
+    }
+
+    #[test]
+    fn texture_definitions_can_be_parsed() {
+        let data = texture1("STARTAN3", 128, 64, &[(0, 0, 0), (64, -8, 1)]);
+        let textures = TextureDefinition::parse_lump(&data).unwrap();
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures[0].name, "STARTAN3");
+        assert_eq!((textures[0].width, textures[0].height), (128, 64));
+        assert_eq!(
+            textures[0].patches[1],
+            TexturePatch {
+                origin_x: 64,
+                origin_y: -8,
+                patch: 1
+            }
+        );
+        assert!(TextureDefinition::parse_lump(&data[..30]).is_err());
+    }
+
+    /// Builds a Strife TEXTURE1 lump with a single texture
+    fn strife_texture1(
+        name: &str,
+        width: i16,
+        height: i16,
+        patches: &[(i16, i16, i16)],
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(&1i32.to_le_bytes());
+        data.extend(&8i32.to_le_bytes());
+        let mut bytes = [0u8; 8];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        data.extend(&bytes);
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&width.to_le_bytes());
+        data.extend(&height.to_le_bytes());
+        data.extend(&(patches.len() as i16).to_le_bytes());
+        for (x, y, patch) in patches {
+            data.extend(&x.to_le_bytes());
+            data.extend(&y.to_le_bytes());
+            data.extend(&patch.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn texture_format_is_detected() {
+        let doom = texture1("STARTAN3", 128, 64, &[(0, 0, 0), (64, 0, 1)]);
+        assert_eq!(TextureFormat::detect(&doom).unwrap(), TextureFormat::Doom);
+
+        // the first patch origin ends up in the column directory
+        let strife = strife_texture1("BRKGRY01", 64, 128, &[(0, 3, 1), (32, 0, 0)]);
+        assert_eq!(
+            TextureFormat::detect(&strife).unwrap(),
+            TextureFormat::Strife
+        );
+
+        // patches at the origin are only detected by their size
+        let strife = strife_texture1("BRKGRY02", 64, 128, &[(0, 0, 1), (0, 0, 0)]);
+        assert_eq!(
+            TextureFormat::detect(&strife).unwrap(),
+            TextureFormat::Strife
+        );
+    }
+
+    /// Builds a TEXTUREx lump from the definitions of single texture lumps
+    fn texture_lump(lumps: &[Vec<u8>]) -> Vec<u8> {
+        let mut offset = 4 + lumps.len() * 4;
+        let mut data = (lumps.len() as i32).to_le_bytes().to_vec();
+        for lump in lumps {
+            data.extend(&(offset as i32).to_le_bytes());
+            offset += lump.len() - 8;
+        }
+        for lump in lumps {
+            data.extend(&lump[8..]);
+        }
+        data
+    }
+
+    #[test]
+    fn textures_without_patches_are_valid() {
+        let data = texture_lump(&[
+            texture1("STARTAN3", 128, 64, &[(0, 0, 0)]),
+            texture1("AASHITTY", 64, 64, &[]),
+            texture1("STARTAN2", 128, 64, &[(0, 0, 1), (64, 0, 0)]),
+        ]);
+        assert_eq!(TextureFormat::detect(&data).unwrap(), TextureFormat::Doom);
+        let textures = TextureDefinition::parse_lump(&data).unwrap();
+        assert_eq!(textures[1].name, "AASHITTY");
+        assert!(textures[1].patches.is_empty());
+        assert_eq!(textures[2].patches.len(), 2);
+    }
+
+    #[test]
+    fn nameless_alpha_textures_can_be_parsed() {
+        // the Doom layout with the 8 byte names cut out
+        let nameless = |width, height, patches: &[(i16, i16, i16)]| {
+            let mut data = texture1("X", width, height, patches);
+            data.drain(8..16);
+            data
+        };
+        let data = texture_lump(&[
+            nameless(64, 128, &[(0, 0, 0)]),
+            nameless(128, 64, &[(0, 0, 1), (64, -8, 0)]),
+        ]);
+        assert_eq!(
+            TextureFormat::detect(&data).unwrap(),
+            TextureFormat::Nameless
+        );
+
+        let textures = TextureDefinition::parse_lump(&data).unwrap();
+        assert_eq!(textures[0].name, "TEX0000");
+        assert_eq!((textures[1].width, textures[1].height), (128, 64));
+        assert_eq!(
+            textures[1].patches[1],
+            TexturePatch {
+                origin_x: 64,
+                origin_y: -8,
+                patch: 0
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_texture_layouts_are_rejected() {
+        // the patch list is cut off, so the lump fits neither layout
+        let mut data = texture1("STARTAN3", 128, 64, &[(0, 0, 0), (64, 0, 1)]);
+        data.truncate(data.len() - 12);
+        assert!(TextureFormat::detect(&data).is_err());
+        assert!(TextureDefinition::parse_lump(&data).is_err());
+
+        // a definition without a size
+        let data = texture1("STARTAN3", 0, 0, &[(0, 0, 0)]);
+        assert!(TextureFormat::detect(&data).is_err());
+    }
+
+    #[test]
+    fn strife_texture_definitions_can_be_parsed() {
+        let data = strife_texture1("BRKGRY01", 64, 128, &[(0, 3, 1), (32, -4, 0)]);
+        let textures = TextureDefinition::parse_lump(&data).unwrap();
+        assert_eq!(textures[0].name, "BRKGRY01");
+        assert_eq!((textures[0].width, textures[0].height), (64, 128));
+        assert_eq!(textures[0].patches.len(), 2);
+        assert_eq!(
+            textures[0].patches[1],
+            TexturePatch {
+                origin_x: 32,
+                origin_y: -4,
+                patch: 0
+            }
+        );
+    }
+
+    #[test]
+    fn texture_composition_overlaps_and_reports_missing_patches() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        wad.add_lump("WALL1", patch(4, 4, 0, 4, 1)).unwrap();
+        wad.add_marker("P_START").unwrap();
+        wad.add_lump("WALL2", patch(2, 2, 0, 2, 2)).unwrap();
+        wad.add_marker("P_END").unwrap();
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+
+        let names = PatchNames::from_bytes(&pnames(&["WALL1", "WALL2", "GONE"])).unwrap();
+        let texture = texture1("TEST", 4, 4, &[(0, 0, 0), (1, 1, 1), (0, 0, 2), (3, -1, 1)]);
+        let texture = &TextureDefinition::parse_lump(&texture).unwrap()[0];
+        let composed = texture.compose(&names, &index).unwrap();
+
+        assert_eq!(composed.missing_patches, vec!["GONE".to_string()]);
+        let image = &composed.image;
+        assert_eq!(image.get(0, 0), Some(1));
+        // the later patch covers the earlier one
+        assert_eq!(image.get(1, 1), Some(2));
+        assert_eq!(image.get(2, 2), Some(2));
+        assert_eq!(image.get(3, 3), Some(1));
+        // negative y origin: the post is drawn from the top and loses its last row
+        assert_eq!(image.get(3, 0), Some(2));
+        assert_eq!(image.get(3, 1), Some(1));
+    }
+
+    #[test]
+    fn texture_composition_fails_on_unknown_patch_index() {
+        let data = WadWriter::new(MagicString::PWAD).unwrap().finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let names = PatchNames::from_bytes(&pnames(&["WALL1"])).unwrap();
+        let texture = texture1("TEST", 4, 4, &[(0, 0, 5)]);
+        let texture = &TextureDefinition::parse_lump(&texture).unwrap()[0];
+        assert!(texture.compose(&names, &index).is_err());
+    }
+}
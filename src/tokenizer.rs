@@ -1,5 +1,6 @@
-use crate::header::Header;
-use crate::lump::{LUMP_ENTRY_LENGTH, LumpRef};
+use crate::header::{ByteOrder, Header};
+use crate::lump::{LumpRef, TEXTURE_LUMP_NAME_LENGTH};
+use std::borrow::Cow;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
@@ -9,7 +10,9 @@ pub enum LumpToken<'a> {
     MarkerStart(&'a str),
     MarkerEnd(&'a str),
     MapMarker(&'a str),
-    Lump(&'a str, LumpRef<'a>),
+    /// Names of compressed lumps have to be rebuilt without the compression flag,
+    /// so they are owned, all other names borrow from the WAD data
+    Lump(Cow<'a, str>, LumpRef<'a>),
 }
 
 impl LumpToken<'_> {
@@ -22,6 +25,19 @@ impl LumpToken<'_> {
     }
 }
 
+/// Jaguar and Doom 64 WADs flag LZSS compressed lumps with the high bit of the first name byte
+const COMPRESSED_FLAG: u8 = 0x80;
+
+/// Checks for the compression flag on an otherwise ASCII name. The Doom 64 IWAD is
+/// little-endian, so the flag can't be told apart by the byte order. A name whose other
+/// bytes aren't ASCII is no flagged name but UTF-8.
+fn has_compressed_flag(name_bytes: &[u8; 8]) -> bool {
+    let length = name_bytes.iter().position(|&b| b == 0).unwrap_or(8);
+    name_bytes[0] & COMPRESSED_FLAG != 0
+        && (name_bytes[0] & !COMPRESSED_FLAG).is_ascii_graphic()
+        && name_bytes[1..length].iter().all(u8::is_ascii_graphic)
+}
+
 fn is_map_marker(name: &str) -> bool {
     match name.as_bytes() {
         [b'M', b'A', b'P', d1, d2] => d1.is_ascii_digit() && d2.is_ascii_digit(),
@@ -32,24 +48,116 @@ fn is_map_marker(name: &str) -> bool {
 
 pub struct TokenIterator<'a> {
     data: &'a [u8],
+    directory_start: usize,
     directory_offset: usize,
     directory_end: usize,
+    entry_length: usize,
+    texture_wad: bool,
+    byte_order: ByteOrder,
 }
 
 impl<'a> TokenIterator<'a> {
     pub fn new(header: Header, data: &'a [u8]) -> Result<Self> {
+        Self::with_byte_order(header, data, ByteOrder::LittleEndian)
+    }
+
+    /// Creates a TokenIterator for WADs whose directory fields use the given byte order
+    pub fn with_byte_order(header: Header, data: &'a [u8], byte_order: ByteOrder) -> Result<Self> {
+        let entry_length = header.identification.directory_entry_length();
         let directory_offset = header.info_table_offset as usize;
-        let directory_end = directory_offset + (header.num_lumps as usize * LUMP_ENTRY_LENGTH);
+        let directory_end = directory_offset + (header.num_lumps as usize * entry_length);
         if data.len() < directory_end {
             Err("Data too small to contain directory entries".into())
         } else {
             Ok(TokenIterator {
                 data,
+                directory_start: directory_offset,
                 directory_offset,
                 directory_end,
+                entry_length,
+                texture_wad: header.identification.is_texture_wad(),
+                byte_order,
             })
         }
     }
+
+    /// Reads a 32 byte WAD2/WAD3 directory entry.
+    ///
+    /// # Entry Layout
+    /// - Bytes 0-3: Offset of the lump data (i32, little-endian)
+    /// - Bytes 4-7: Size of the lump data in the file (i32, little-endian)
+    /// - Bytes 8-11: Uncompressed size of the lump data (i32, little-endian)
+    /// - Byte 12: Lump type
+    /// - Byte 13: Compression (0 = none)
+    /// - Bytes 14-15: Padding
+    /// - Bytes 16-31: Zero terminated lump name
+    ///
+    /// Texture WADs have no marker lumps, every entry becomes a `LumpToken::Lump`.
+    fn read_texture_entry(&self, entry_offset: usize) -> Result<LumpToken<'a>> {
+        let entry = &self.data[entry_offset..entry_offset + self.entry_length];
+        let pos = usize::try_from(i32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]))
+            .map_err(|_| "Negative lump offset")?;
+        let len = usize::try_from(i32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]))
+            .map_err(|_| "Negative lump size")?;
+        let lump_type = entry[12];
+        let compression = entry[13];
+        if compression != 0 {
+            return Err("Compressed WAD2/WAD3 lumps are not supported".into());
+        }
+
+        // names are zero terminated, the bytes after the terminator are often garbage
+        let name_bytes = &entry[16..16 + TEXTURE_LUMP_NAME_LENGTH];
+        let name_len = name_bytes
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(TEXTURE_LUMP_NAME_LENGTH);
+        let name = std::str::from_utf8(&name_bytes[..name_len])?;
+
+        let data = pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(pos..end))
+            .ok_or("Lump data out of range")?;
+
+        Ok(LumpToken::Lump(
+            name.into(),
+            LumpRef::with_type(data, name, lump_type)
+                .with_entry_index(self.entry_index(entry_offset)),
+        ))
+    }
+
+    /// Builds the token of an LZSS compressed lump. The directory only stores the
+    /// decompressed size, the compressed stream runs until its end marker, which lies
+    /// before the directory for lumps stored in front of it.
+    fn read_compressed_entry(
+        &self,
+        entry_offset: usize,
+        name_bytes: &[u8; 8],
+        pos: usize,
+        size: usize,
+    ) -> Result<LumpToken<'a>> {
+        let mut name_bytes = *name_bytes;
+        name_bytes[0] &= !COMPRESSED_FLAG;
+        let name = std::str::from_utf8(&name_bytes)?
+            .trim_end_matches('\0')
+            .to_string();
+
+        let end = if pos < self.directory_start {
+            self.directory_start
+        } else {
+            self.data.len()
+        };
+        let data = self.data.get(pos..end).ok_or("Lump data out of range")?;
+
+        Ok(LumpToken::Lump(
+            Cow::Owned(name.clone()),
+            LumpRef::compressed(data, name, size).with_entry_index(self.entry_index(entry_offset)),
+        ))
+    }
+
+    /// Position of a directory entry, counted from the first entry
+    fn entry_index(&self, entry_offset: usize) -> usize {
+        (entry_offset - self.directory_start) / self.entry_length
+    }
 }
 
 impl<'a> Iterator for TokenIterator<'a> {
@@ -61,11 +169,15 @@ impl<'a> Iterator for TokenIterator<'a> {
         }
 
         let entry_offset = self.directory_offset;
-        self.directory_offset += LUMP_ENTRY_LENGTH;
+        self.directory_offset += self.entry_length;
+
+        if self.texture_wad {
+            return Some(self.read_texture_entry(entry_offset));
+        }
 
         // Safety: We are reading exactly 8 bytes from a valid slice of data we checked in new()
         // the overall data length is at least directory_end
-        let (pos_bytes, len_bytes, name) = unsafe {
+        let (pos_bytes, len_bytes, name_bytes) = unsafe {
             let pos_ptr = self.data.as_ptr().add(entry_offset);
             let len_ptr = pos_ptr.add(4);
             let name_ptr = len_ptr.add(4);
@@ -73,19 +185,40 @@ impl<'a> Iterator for TokenIterator<'a> {
             let pos_bytes: &[u8; 4] = &*(pos_ptr as *const [u8; 4]);
             let len_bytes: &[u8; 4] = &*(len_ptr as *const [u8; 4]);
 
-            (
-                pos_bytes,
-                len_bytes,
-                std::str::from_utf8_unchecked(name_bytes).trim_end_matches('\0'),
-            )
+            (pos_bytes, len_bytes, name_bytes)
+        };
+        let (pos, len) = match self.byte_order {
+            ByteOrder::LittleEndian => (
+                i32::from_le_bytes(*pos_bytes),
+                i32::from_le_bytes(*len_bytes),
+            ),
+            ByteOrder::BigEndian => (
+                i32::from_be_bytes(*pos_bytes),
+                i32::from_be_bytes(*len_bytes),
+            ),
         };
-        let pos = i32::from_le_bytes(*pos_bytes) as usize;
-        let len = i32::from_le_bytes(*len_bytes) as usize;
+        let (pos, len) = (pos as usize, len as usize);
+
+        // only Jaguar (big-endian) and Doom 64 (little-endian) WADs compress lumps
+        if has_compressed_flag(name_bytes) {
+            return Some(self.read_compressed_entry(entry_offset, name_bytes, pos, len));
+        }
+
+        let name = match std::str::from_utf8(name_bytes) {
+            Ok(name) => name.trim_end_matches('\0'),
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        // Jaguar and Doom 64 WADs close the directory with an empty ENDOFWAD lump
+        if len == 0 && name == "ENDOFWAD" {
+            self.directory_offset = self.directory_end;
+            return None;
+        }
+
         let data = &self.data[pos..pos + len];
 
-        let lump_ref = LumpRef::new(data, name);
+        let lump_ref = LumpRef::new(data, name).with_entry_index(self.entry_index(entry_offset));
 
-        let name = lump_ref.name();
         if len == 0 { // Marker lump
             if is_map_marker(&name) {
                 Some(Ok(LumpToken::MapMarker(name)))
@@ -94,10 +227,11 @@ impl<'a> Iterator for TokenIterator<'a> {
             } else if LumpToken::is_end_marker(&name) {
                 Some(Ok(LumpToken::MarkerEnd(name)))
             } else {
-                Some(Err("Unknown marker type".into()))
+                // empty lumps like ENDMAP of UDMF maps or GL_MAP01 of GL nodes
+                Some(Ok(LumpToken::Lump(name.into(), lump_ref)))
             }
         } else {
-            Some(Ok(LumpToken::Lump(name, lump_ref)))
+            Some(Ok(LumpToken::Lump(name.into(), lump_ref)))
         }
     }
 }
@@ -160,4 +294,148 @@ mod tests {
             _ => panic!("Expected MapMarker token for E1M2"),
         }
     }
+
+    #[test]
+    fn tokenize_big_endian_wad_decodes_compressed_lumps() {
+        let header = Header {
+            identification: MagicString::IWAD,
+            num_lumps: 2,
+            info_table_offset: 8,
+        };
+        let mut data = vec![0b0000_0000, b'D', b'O', b'O', b'M', 0, 0, 0];
+        data.extend(&0i32.to_be_bytes()); // pos
+        data.extend(&4i32.to_be_bytes()); // decompressed size
+        data.extend(&[b'C' | COMPRESSED_FLAG, b'O', b'M', b'P', 0, 0, 0, 0]);
+        data.extend(&0i32.to_be_bytes());
+        data.extend(&0i32.to_be_bytes());
+        data.extend(b"ENDOFWAD");
+
+        let mut tokens =
+            TokenIterator::with_byte_order(header, &data, ByteOrder::BigEndian).unwrap();
+        match tokens.next().unwrap().unwrap() {
+            LumpToken::Lump(name, lump_ref) => {
+                assert_eq!(name, "COMP");
+                assert!(lump_ref.is_compressed());
+                assert_eq!(lump_ref.size(), 4);
+                assert_eq!(lump_ref.decompressed().unwrap().as_ref(), b"DOOM");
+            }
+            _ => panic!("Expected Lump token for COMP"),
+        }
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn tokenize_little_endian_wad_decodes_compressed_lumps() {
+        // like the Doom 64 IWAD
+        let header = Header {
+            identification: MagicString::IWAD,
+            num_lumps: 1,
+            info_table_offset: 8,
+        };
+        let mut data = vec![0b0000_0000, b'D', b'O', b'O', b'M', 0, 0, 0];
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&4i32.to_le_bytes());
+        data.extend(&[b'C' | COMPRESSED_FLAG, b'O', b'M', b'P', 0, 0, 0, 0]);
+
+        let mut tokens = TokenIterator::new(header, &data).unwrap();
+        match tokens.next().unwrap().unwrap() {
+            LumpToken::Lump(name, lump_ref) => {
+                assert_eq!(name, "COMP");
+                assert!(lump_ref.is_compressed());
+                assert_eq!(lump_ref.decompressed().unwrap().as_ref(), b"DOOM");
+            }
+            _ => panic!("Expected Lump token for COMP"),
+        }
+    }
+
+    #[test]
+    fn tokenize_utf8_names_are_not_compressed() {
+        let header = Header {
+            identification: MagicString::PWAD,
+            num_lumps: 1,
+            info_table_offset: 4,
+        };
+        let mut data = vec![1, 2, 3, 4];
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&4i32.to_le_bytes());
+        data.extend("ÄRGER".as_bytes());
+        data.extend(&[0, 0]);
+
+        let mut tokens = TokenIterator::new(header, &data).unwrap();
+        match tokens.next().unwrap().unwrap() {
+            LumpToken::Lump(name, lump_ref) => {
+                assert_eq!(name, "ÄRGER");
+                assert!(!lump_ref.is_compressed());
+            }
+            _ => panic!("Expected Lump token for ÄRGER"),
+        }
+    }
+
+    #[test]
+    fn tokenize_texture_wad_produces_lump_tokens_with_type() {
+        let header = Header {
+            identification: MagicString::WAD3,
+            num_lumps: 1,
+            info_table_offset: 4,
+        };
+        let mut data = vec![1, 2, 3, 4];
+        data.extend(&0i32.to_le_bytes()); // pos
+        data.extend(&4i32.to_le_bytes()); // disk size
+        data.extend(&4i32.to_le_bytes()); // size
+        data.extend(&[0x43, 0, 0, 0]); // type, compression, padding
+        data.extend(b"{FENCE\0GARBAGE!!"); // name with garbage after the terminator
+
+        let mut tokens = TokenIterator::new(header, &data).unwrap();
+        match tokens.next().unwrap().unwrap() {
+            LumpToken::Lump(name, lump_ref) => {
+                assert_eq!(name, "{FENCE");
+                assert_eq!(lump_ref.lump_type(), 0x43);
+                assert_eq!(lump_ref.data(), &[1, 2, 3, 4]);
+            }
+            _ => panic!("Expected Lump token for {{FENCE"),
+        }
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn tokenize_texture_wad_rejects_compressed_lumps() {
+        let header = Header {
+            identification: MagicString::WAD2,
+            num_lumps: 1,
+            info_table_offset: 0,
+        };
+        let mut data = Vec::new();
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&0i32.to_le_bytes());
+        data.extend(&[0x44, 1, 0, 0]);
+        data.extend(b"COMPRESSED\0\0\0\0\0\0");
+
+        let mut tokens = TokenIterator::new(header, &data).unwrap();
+        assert!(tokens.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn tokenize_texture_wad_rejects_negative_offsets_and_sizes() {
+        let header = Header {
+            identification: MagicString::WAD3,
+            num_lumps: 1,
+            info_table_offset: 0,
+        };
+        let entry = |pos: i32, size: i32| {
+            let mut data = Vec::new();
+            data.extend(&pos.to_le_bytes());
+            data.extend(&size.to_le_bytes());
+            data.extend(&size.to_le_bytes());
+            data.extend(&[0x43, 0, 0, 0]);
+            data.extend(b"BROKEN\0\0\0\0\0\0\0\0\0\0");
+            data
+        };
+
+        for (pos, size) in [(4, -1), (-4, 4), (8, i32::MAX)] {
+            let data = entry(pos, size);
+            let mut tokens = TokenIterator::new(header, &data).unwrap();
+            assert!(tokens.next().unwrap().is_err());
+        }
+    }
 }
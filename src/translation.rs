@@ -0,0 +1,288 @@
+use crate::graphics::{IndexedImage, Palette};
+use crate::index::{LumpNode, LumpSource};
+
+type Error = Box<dyn std::error::Error>;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Size of a translation table in bytes
+pub const TRANSLATION_SIZE: usize = 256;
+
+/// Number of players with their own color in Doom and Heretic
+pub const PLAYER_COLORS: usize = 4;
+
+/// Number of players in Hexen, player 1 is drawn untranslated
+const HEXEN_PLAYERS: usize = 8;
+
+/// Number of player classes in Hexen (fighter, cleric, mage)
+const HEXEN_CLASSES: usize = 3;
+
+/// First index of the green player range in the Doom palette
+const DOOM_GREEN_START: u8 = 0x70;
+/// Start of the gray, brown and red ranges which replace the green range
+const DOOM_PLAYER_RANGES: [u8; 3] = [0x60, 0x40, 0x20];
+/// Number of indices in the Doom player range
+const DOOM_RANGE_SIZE: u8 = 16;
+
+/// First index of the green player range in the Heretic palette
+const HERETIC_GREEN_START: u8 = 225;
+/// Start of the yellow, red and blue ranges which replace the green range
+const HERETIC_PLAYER_RANGES: [u8; 3] = [114, 145, 190];
+/// Number of indices in the Heretic player range
+const HERETIC_RANGE_SIZE: u8 = 16;
+
+/// Maps each palette index to the palette index which is drawn instead.
+///
+/// The engines use translations to draw the players of a multiplayer game in different
+/// colors with the same sprites. Doom and Heretic build their tables in code, Hexen loads
+/// them from the TRANTBLx lumps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    table: [u8; TRANSLATION_SIZE],
+}
+
+impl Translation {
+    /// The translation which keeps every index
+    pub fn identity() -> Self {
+        let mut table = [0u8; TRANSLATION_SIZE];
+        for (index, entry) in table.iter_mut().enumerate() {
+            *entry = index as u8;
+        }
+
+        Self { table }
+    }
+
+    /// Creates a `Translation` from a TRANTBLx lump.
+    ///
+    /// # Arguments
+    /// - `data`: The lump data, one palette index for each of the 256 palette indices.
+    /// # Returns
+    /// - `Ok(Translation)` with the table of the lump.
+    /// - `Err` if the lump is shorter than 256 bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let table = data
+            .get(..TRANSLATION_SIZE)
+            .ok_or("Translation data too short")?
+            .try_into()?;
+
+        Ok(Self { table })
+    }
+
+    /// Returns the vanilla Doom translation of a player.
+    ///
+    /// # Arguments
+    /// - `player`: The player number, 0 (green), 1 (gray), 2 (brown) or 3 (red).
+    /// # Returns
+    /// - `Some(Translation)` which remaps the green range 0x70-0x7F, player 0 keeps it.
+    /// - `None` if the player number is out of range.
+    pub fn doom_player(player: usize) -> Option<Self> {
+        Self::player_translation(
+            player,
+            DOOM_GREEN_START,
+            &DOOM_PLAYER_RANGES,
+            DOOM_RANGE_SIZE,
+        )
+    }
+
+    /// Returns the vanilla Heretic translation of a player.
+    ///
+    /// # Arguments
+    /// - `player`: The player number, 0 (green), 1 (yellow), 2 (red) or 3 (blue).
+    /// # Returns
+    /// - `Some(Translation)` which remaps the green range 225-240, player 0 keeps it.
+    /// - `None` if the player number is out of range.
+    pub fn heretic_player(player: usize) -> Option<Self> {
+        Self::player_translation(
+            player,
+            HERETIC_GREEN_START,
+            &HERETIC_PLAYER_RANGES,
+            HERETIC_RANGE_SIZE,
+        )
+    }
+
+    fn player_translation(player: usize, start: u8, ranges: &[u8], size: u8) -> Option<Self> {
+        let mut translation = Self::identity();
+        if player == 0 {
+            return Some(translation);
+        }
+
+        let range_start = *ranges.get(player - 1)?;
+        for offset in 0..size {
+            translation.table[(start + offset) as usize] = range_start + offset;
+        }
+
+        Some(translation)
+    }
+
+    pub fn table(&self) -> &[u8; TRANSLATION_SIZE] {
+        &self.table
+    }
+
+    /// Maps a single palette index
+    pub fn translate(&self, index: u8) -> u8 {
+        self.table[index as usize]
+    }
+
+    /// Maps palette indices in place, e.g. a decoded flat or patch column
+    pub fn translate_indices(&self, pixels: &mut [u8]) {
+        for pixel in pixels {
+            *pixel = self.table[*pixel as usize];
+        }
+    }
+
+    /// Maps the pixels of a decoded sprite in place, the transparency mask is kept
+    pub fn translate_image(&self, image: &mut IndexedImage) {
+        self.translate_indices(image.pixels_mut());
+    }
+
+    /// Builds a palette which already applies the translation, so any decoder that takes
+    /// a `Palette` draws translated pictures.
+    pub fn translated_palette(&self, palette: &Palette) -> Result<Palette<'static>> {
+        let mut colors = [[0u8; 3]; 256];
+        for (color, &index) in colors.iter_mut().zip(&self.table) {
+            *color = *palette
+                .get_rgb(index as usize)
+                .ok_or("palette index out of bounds")?;
+        }
+
+        Ok(Palette::from_colors(colors))
+    }
+}
+
+impl Default for Translation {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl TryFrom<&[u8]> for Translation {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        Translation::from_bytes(value)
+    }
+}
+
+/// The player translations of Hexen, loaded from the TRANTBLx lumps.
+///
+/// Hexen has a table for each class and player color except the first player, who is
+/// drawn untranslated. The 21 lumps are named TRANTBL0 to TRANTBL9 and TRANTBLA to
+/// TRANTBLK, ordered by class and then by player.
+#[derive(Debug, Clone)]
+pub struct HexenTranslations {
+    tables: Vec<Translation>,
+}
+
+impl HexenTranslations {
+    /// Loads the TRANTBLx lumps of a lump tree.
+    ///
+    /// # Returns
+    /// - `Ok(HexenTranslations)` with the tables of all classes.
+    /// - `Err` if a lump is missing, too short or can't be decompressed.
+    pub fn from_source<'a, S: LumpSource<'a>>(source: &S) -> Result<Self> {
+        let mut tables = Vec::with_capacity(HEXEN_CLASSES * (HEXEN_PLAYERS - 1));
+        for name in trantbl_names() {
+            let Some(LumpNode::Lump { lump, .. }) = source.get_lump(vec![], &name) else {
+                return Err(format!("translation lump {name} missing").into());
+            };
+            tables.push(Translation::from_bytes(&lump.decompressed()?)?);
+        }
+
+        Ok(Self { tables })
+    }
+
+    /// Returns the translation of a player.
+    ///
+    /// # Arguments
+    /// - `class`: The player class, 0 (fighter), 1 (cleric) or 2 (mage).
+    /// - `player`: The player number, 0-7.
+    /// # Returns
+    /// - `Some(Translation)`, the identity for player 0.
+    /// - `None` if the class or player is out of range.
+    pub fn get(&self, class: usize, player: usize) -> Option<Translation> {
+        if class >= HEXEN_CLASSES || player >= HEXEN_PLAYERS {
+            return None;
+        }
+
+        match player {
+            0 => Some(Translation::identity()),
+            _ => self
+                .tables
+                .get(class * (HEXEN_PLAYERS - 1) + player - 1)
+                .cloned(),
+        }
+    }
+}
+
+/// Names of the Hexen translation lumps in the order the engine loads them
+fn trantbl_names() -> impl Iterator<Item = String> {
+    (b'0'..=b'9')
+        .chain(b'A'..=b'Z')
+        .take(HEXEN_CLASSES * (HEXEN_PLAYERS - 1))
+        .map(|suffix| format!("TRANTBL{}", suffix as char))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::MagicString;
+    use crate::sprite::Sprite;
+    use crate::wad::{WadIndex, WadWriter};
+
+    #[test]
+    fn doom_player_translations_remap_green_range() {
+        let gray = Translation::doom_player(1).unwrap();
+        assert_eq!(gray.translate(0x70), 0x60);
+        assert_eq!(gray.translate(0x7F), 0x6F);
+        assert_eq!(gray.translate(0x80), 0x80);
+        assert_eq!(Translation::doom_player(2).unwrap().translate(0x75), 0x45);
+        assert_eq!(Translation::doom_player(3).unwrap().translate(0x75), 0x25);
+        assert_eq!(
+            Translation::doom_player(0).unwrap(),
+            Translation::identity()
+        );
+        assert!(Translation::doom_player(PLAYER_COLORS).is_none());
+    }
+
+    #[test]
+    fn heretic_player_translations_remap_green_range() {
+        let yellow = Translation::heretic_player(1).unwrap();
+        assert_eq!(yellow.translate(225), 114);
+        assert_eq!(yellow.translate(240), 129);
+        assert_eq!(yellow.translate(241), 241);
+        assert_eq!(Translation::heretic_player(3).unwrap().translate(230), 195);
+    }
+
+    #[test]
+    fn translation_applies_to_decoded_sprites() {
+        // 1x2 patch with a green pixel and a transparent pixel
+        let mut data = vec![1, 0, 2, 0, 0, 0, 0, 0];
+        data.extend(&12u32.to_le_bytes());
+        data.extend(&[0, 1, 0, 0x72, 0, 0xFF]);
+        let mut image = Sprite::new(&data).unwrap().indexed_image().unwrap();
+
+        Translation::doom_player(3)
+            .unwrap()
+            .translate_image(&mut image);
+        assert_eq!(image.get(0, 0), Some(0x22));
+        assert_eq!(image.get(0, 1), None);
+    }
+
+    #[test]
+    fn hexen_translations_load_trantbl_lumps() {
+        let mut wad = WadWriter::new(MagicString::PWAD).unwrap();
+        let names: Vec<String> = trantbl_names().collect();
+        assert_eq!(names.last().unwrap(), "TRANTBLK");
+        for (i, name) in names.iter().enumerate() {
+            wad.add_lump(name, vec![i as u8; TRANSLATION_SIZE]).unwrap();
+        }
+        let data = wad.finish().unwrap();
+        let index = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        let translations = HexenTranslations::from_source(&index).unwrap();
+
+        assert_eq!(translations.get(0, 0), Some(Translation::identity()));
+        assert_eq!(translations.get(0, 1).unwrap().translate(50), 0);
+        assert_eq!(translations.get(2, 7).unwrap().translate(50), 20);
+        assert!(translations.get(3, 1).is_none());
+        assert!(Translation::from_bytes(&[0u8; 100]).is_err());
+    }
+}
@@ -1,7 +1,9 @@
 use crate::audio::SoundSample;
-use crate::header::{Header, MagicString};
-use crate::index::{LumpNode, index_tokens};
+use crate::header::{ByteOrder, Header, MagicString};
+use crate::index::{LumpNode, LumpSource, index_tokens};
+use crate::lump::{LUMP_ENTRY_LENGTH, LUMP_NAME_LENGTH};
 use crate::tokenizer::TokenIterator;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 type Error = Box<dyn std::error::Error>;
@@ -11,7 +13,7 @@ pub struct WadIndex<'a> {
     header: Header,
     name: String,
     file_type: MagicString,
-    lump_index: HashMap<&'a str, LumpNode<'a>>,
+    lump_index: HashMap<Cow<'a, str>, LumpNode<'a>>,
 }
 
 impl<'a> WadIndex<'a> {
@@ -21,9 +23,18 @@ impl<'a> WadIndex<'a> {
             return Err("Data too small to contain valid WAD header".into());
         }
         let header_bytes: &[u8; 12] = data[0..12].try_into()?;
-        let header = Header::try_from(header_bytes).map_err(|e| e.to_string())?;
+        let mut header = Header::try_from(header_bytes).map_err(|e| e.to_string())?;
+        let mut byte_order = ByteOrder::LittleEndian;
+        if !header.fits(size) {
+            // Jaguar and Doom 64 WADs store the header and directory big-endian
+            let big_endian = Header::from_big_endian(header_bytes)?;
+            if big_endian.fits(size) {
+                header = big_endian;
+                byte_order = ByteOrder::BigEndian;
+            }
+        }
         let file_type = header.identification;
-        let lump_index = index_tokens(TokenIterator::new(header, &data)?)?;
+        let lump_index = index_tokens(TokenIterator::with_byte_order(header, data, byte_order)?)?;
 
         let wad_index = WadIndex {
             header,
@@ -39,35 +50,28 @@ impl<'a> WadIndex<'a> {
         &self.header
     }
 
-    pub fn get_lump_index(&self) -> &HashMap<&'a str, LumpNode<'a>> {
+    pub fn get_lump_index(&self) -> &HashMap<Cow<'a, str>, LumpNode<'a>> {
         &self.lump_index
     }
 
-    pub fn get_lump(&'_ self, namespaces: Vec<&str>, name: &str) -> Option<&LumpNode<'a>> {
-        let mut current_index = &self.lump_index;
-        for namespace in namespaces {
-            if let Some(LumpNode::Namespace { children, .. }) =
-                current_index.get(namespace)
-            {
-                current_index = children;
-            } else {
-                return None;
-            }
-        }
-        current_index.get(name)
+    /// Looks up a lump by its namespace path, see `LumpSource::get_lump`
+    pub fn get_lump(&self, namespaces: Vec<&str>, name: &str) -> Option<&LumpNode<'a>> {
+        LumpSource::get_lump(self, namespaces, name)
     }
 
+    /// Decodes a top level sound lump, see `LumpSource::get_sound_sample`
     pub fn get_sound_sample(&self, name: &str) -> Result<Option<SoundSample>> {
-        if let Some(lump_node) = self.lump_index.get(name) {
-            if let LumpNode::Lump { lump, .. } = lump_node {
-                let lump_data = lump.data();
-                Ok(Some(SoundSample::try_from(lump_data)?))
-            } else {
-                Ok(None)
-            }
-        } else {
-            Ok(None)
-        }
+        LumpSource::get_sound_sample(self, name)
+    }
+
+    /// Returns the lumps of all maps, see `LumpSource::get_maps`
+    pub fn get_maps(&self) -> Option<&HashMap<Cow<'a, str>, LumpNode<'a>>> {
+        LumpSource::get_maps(self)
+    }
+
+    /// Consumes the index and returns the lump tree, used to embed WADs into other containers
+    pub fn into_lump_index(self) -> HashMap<Cow<'a, str>, LumpNode<'a>> {
+        self.lump_index
     }
 
     pub fn get_name(&self) -> &str {
@@ -77,12 +81,136 @@ impl<'a> WadIndex<'a> {
     pub fn get_file_type(&self) -> MagicString {
         self.file_type
     }
+}
+
+impl<'a> LumpSource<'a> for WadIndex<'a> {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_lump_index(&self) -> &HashMap<Cow<'a, str>, LumpNode<'a>> {
+        &self.lump_index
+    }
+}
+
+/// Builds an IWAD or PWAD in memory.
+///
+/// Lumps are written in the order they were added, followed by the directory.
+pub struct WadWriter<'a> {
+    identification: MagicString,
+    lumps: Vec<([u8; LUMP_NAME_LENGTH], Cow<'a, [u8]>)>,
+}
+
+impl<'a> WadWriter<'a> {
+    pub fn new(identification: MagicString) -> Result<Self> {
+        if identification.is_texture_wad() {
+            return Err("WadWriter can only write IWAD and PWAD files".into());
+        }
+
+        Ok(Self {
+            identification,
+            lumps: Vec::new(),
+        })
+    }
 
-    pub fn get_maps(&self) -> Option<&HashMap<&'a str, LumpNode<'a>>> {
-        if let Some(LumpNode::Namespace { children, .. }) = self.lump_index.get("MAPS") {
-            Some(children)
-        } else {
-            None
+    /// Appends a lump, the name must be ASCII with at most 8 characters
+    pub fn add_lump<D: Into<Cow<'a, [u8]>>>(&mut self, name: &str, data: D) -> Result<()> {
+        if name.is_empty() || name.len() > LUMP_NAME_LENGTH || !name.is_ascii() {
+            return Err(format!("Invalid lump name '{}'", name).into());
+        }
+
+        let mut name_bytes = [0u8; LUMP_NAME_LENGTH];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        self.lumps.push((name_bytes, data.into()));
+        Ok(())
+    }
+
+    /// Appends an empty marker lump like a map marker or `S_START`
+    pub fn add_marker(&mut self, name: &str) -> Result<()> {
+        self.add_lump(name, &[][..])
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let data_size: usize = self.lumps.iter().map(|(_, data)| data.len()).sum();
+        let directory_offset = 12 + data_size;
+        let size = directory_offset + self.lumps.len() * LUMP_ENTRY_LENGTH;
+        if size > i32::MAX as usize {
+            return Err("WAD data too large".into());
+        }
+
+        let identification: &[u8; 4] = match self.identification {
+            MagicString::IWAD => b"IWAD",
+            _ => b"PWAD",
+        };
+        let mut wad = Vec::with_capacity(size);
+        wad.extend(identification);
+        wad.extend(&(self.lumps.len() as i32).to_le_bytes());
+        wad.extend(&(directory_offset as i32).to_le_bytes());
+
+        let mut directory: Vec<u8> = Vec::with_capacity(self.lumps.len() * LUMP_ENTRY_LENGTH);
+        for (name, data) in &self.lumps {
+            // markers point to the start of the data area like in id's WADs
+            let pos = if data.is_empty() { 0 } else { wad.len() };
+            directory.extend(&(pos as i32).to_le_bytes());
+            directory.extend(&(data.len() as i32).to_le_bytes());
+            directory.extend(name);
+            wad.extend(data.iter());
+        }
+        wad.extend(directory);
+
+        Ok(wad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wad_writer_rejects_invalid_lump_names() {
+        let mut writer = WadWriter::new(MagicString::PWAD).unwrap();
+        assert!(writer.add_lump("TOOLONGNAME", &[1u8][..]).is_err());
+        assert!(writer.add_lump("", &[1u8][..]).is_err());
+    }
+
+    #[test]
+    fn wad_writer_output_can_be_indexed() {
+        let mut writer = WadWriter::new(MagicString::PWAD).unwrap();
+        writer.add_marker("MAP01").unwrap();
+        writer.add_lump("THINGS", &[1u8, 2, 3][..]).unwrap();
+        writer.add_marker("S_START").unwrap();
+        writer.add_lump("TROOA1", vec![4u8, 5]).unwrap();
+        writer.add_marker("S_END").unwrap();
+        let data = writer.finish().unwrap();
+
+        let wad = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        assert_eq!(wad.get_file_type(), MagicString::PWAD);
+        match wad.get_lump(vec!["MAPS", "MAP01"], "THINGS").unwrap() {
+            LumpNode::Lump { lump, .. } => assert_eq!(lump.data(), &[1, 2, 3]),
+            _ => panic!("THINGS is not a lump"),
+        }
+        assert!(wad.get_lump(vec!["S_START"], "TROOA1").is_some());
+    }
+
+    #[test]
+    fn consecutive_maps_keep_their_first_lump() {
+        let mut writer = WadWriter::new(MagicString::PWAD).unwrap();
+        writer.add_marker("MAP01").unwrap();
+        writer.add_lump("THINGS", &[1u8][..]).unwrap();
+        writer.add_lump("LINEDEFS", &[2u8][..]).unwrap();
+        writer.add_marker("MAP02").unwrap();
+        writer.add_lump("THINGS", &[3u8][..]).unwrap();
+        writer.add_lump("LINEDEFS", &[4u8][..]).unwrap();
+        let data = writer.finish().unwrap();
+
+        let wad = WadIndex::from_bytes("test.wad".to_string(), &data).unwrap();
+        assert_eq!(wad.get_maps().unwrap().len(), 2);
+        for (map, things) in [("MAP01", [1u8]), ("MAP02", [3u8])] {
+            match wad.get_lump(vec!["MAPS", map], "THINGS") {
+                Some(LumpNode::Lump { lump, .. }) => assert_eq!(lump.data(), &things),
+                _ => panic!("THINGS of {} is missing", map),
+            }
+            assert!(wad.get_lump(vec!["MAPS", map], "LINEDEFS").is_some());
         }
     }
 }